@@ -0,0 +1,145 @@
+use core::mem::size_of;
+
+use criterion::measurement::WallTime;
+use criterion::{black_box, criterion_group, criterion_main};
+use criterion::{BenchmarkGroup, BenchmarkId, Criterion, Throughput};
+use diff_in_place::DiffInPlace;
+
+/// An element type the arrays are made of.
+trait Element: Copy + PartialEq + From<u8> {
+    const NAME: &'static str;
+}
+
+impl Element for u8 {
+    const NAME: &'static str = "u8";
+}
+
+impl Element for u16 {
+    const NAME: &'static str = "u16";
+}
+
+impl Element for u32 {
+    const NAME: &'static str = "u32";
+}
+
+impl Element for f32 {
+    const NAME: &'static str = "f32";
+}
+
+/// How the elements of the two arrays differ.
+#[derive(Copy, Clone)]
+enum Pattern {
+    /// Every element is equal.
+    Equal,
+    /// Every element is different.
+    Different,
+    /// About one element in a hundred is different, at random.
+    Sparse,
+    /// Runs of 16 different elements, every 256 elements.
+    Clustered,
+    /// Every other element is different.
+    Alternating,
+}
+
+const PATTERNS: [(&str, Pattern); 5] = [
+    ("equal", Pattern::Equal),
+    ("different", Pattern::Different),
+    ("sparse", Pattern::Sparse),
+    ("clustered", Pattern::Clustered),
+    ("alternating", Pattern::Alternating),
+];
+
+impl Pattern {
+    fn is_different(self, idx: usize, random: &mut u32) -> bool {
+        match self {
+            Pattern::Equal => false,
+            Pattern::Different => true,
+            Pattern::Sparse => {
+                // A fixed linear congruential generator, so every run sees the same arrays
+                *random = random.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (*random >> 16) % 100 == 0
+            }
+            Pattern::Clustered => idx % 256 < 16,
+            Pattern::Alternating => idx % 2 == 1,
+        }
+    }
+}
+
+/// Create the pair of arrays to diff, on the heap since the largest are 64 KiB each.
+fn arrays<T, const N: usize>(pattern: Pattern) -> (Box<[T; N]>, Box<[T; N]>)
+where
+    T: Element,
+{
+    let left = Box::new([T::from(0); N]);
+    let mut right = left.clone();
+    let mut random = 1;
+    for (idx, element) in right.iter_mut().enumerate() {
+        if pattern.is_different(idx, &mut random) {
+            *element = T::from(1);
+        }
+    }
+    (left, right)
+}
+
+fn bench_size<T, const N: usize>(group: &mut BenchmarkGroup<'_, WallTime>, pattern: Pattern)
+where
+    T: Element,
+{
+    let (left, right) = arrays::<T, N>(pattern);
+    let bytes = N * size_of::<T>();
+
+    group.throughput(Throughput::Bytes(bytes as u64));
+    group.bench_with_input(BenchmarkId::from_parameter(bytes), &bytes, |b, _| {
+        b.iter(|| {
+            black_box(&left).diff_in_place(black_box(&right), |idx, diff| {
+                black_box((idx, diff));
+            })
+        })
+    });
+}
+
+fn bench_element<T>(c: &mut Criterion)
+where
+    T: Element,
+{
+    for (name, pattern) in PATTERNS {
+        let mut group = c.benchmark_group(format!("{}/{}", T::NAME, name));
+
+        // From 16 bytes to 64 KiB, whatever the size of the elements
+        const KIB: usize = 1024;
+        let size = size_of::<T>();
+        match size {
+            1 => {
+                bench_size::<T, 16>(&mut group, pattern);
+                bench_size::<T, 256>(&mut group, pattern);
+                bench_size::<T, { 4 * KIB }>(&mut group, pattern);
+                bench_size::<T, { 64 * KIB }>(&mut group, pattern);
+            }
+            2 => {
+                bench_size::<T, 8>(&mut group, pattern);
+                bench_size::<T, 128>(&mut group, pattern);
+                bench_size::<T, { 2 * KIB }>(&mut group, pattern);
+                bench_size::<T, { 32 * KIB }>(&mut group, pattern);
+            }
+            4 => {
+                bench_size::<T, 4>(&mut group, pattern);
+                bench_size::<T, 64>(&mut group, pattern);
+                bench_size::<T, KIB>(&mut group, pattern);
+                bench_size::<T, { 16 * KIB }>(&mut group, pattern);
+            }
+            _ => unreachable!("no {}-byte element types are benchmarked", size),
+        }
+
+        group.finish();
+    }
+}
+
+fn benches(c: &mut Criterion) {
+    bench_element::<u8>(c);
+    bench_element::<u16>(c);
+    bench_element::<u32>(c);
+    bench_element::<f32>(c);
+}
+
+criterion_group!(diff, benches);
+criterion_main!(diff);
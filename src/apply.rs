@@ -0,0 +1,209 @@
+use core::fmt;
+
+/// The error returned when a patch cannot be applied.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PatchError {
+    /// A run ends past the end of the array, with the index of the run.
+    OutOfRange(usize),
+    /// A run starts before the previous run, with the index of the run.
+    Unordered(usize),
+    /// A run starts before the previous run ends, with the index of the run.
+    Overlapping(usize),
+    /// The checksum of the array the patch applies to does not match.
+    ChecksumMismatch,
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::OutOfRange(idx) => write!(f, "run at {} ends out of range", idx),
+            PatchError::Unordered(idx) => write!(f, "run at {} is out of order", idx),
+            PatchError::Overlapping(idx) => write!(f, "run at {} overlaps the previous run", idx),
+            PatchError::ChecksumMismatch => f.write_str("checksum of the base does not match"),
+        }
+    }
+}
+
+/// Validate the given runs and apply them to an array.
+///
+/// Every run is validated before any is applied, so the array is left untouched if the
+/// runs are invalid. The runs must lie within the array, and be ordered without overlap,
+/// as is the case for the runs of [`DiffInPlace::diff_runs`](crate::DiffInPlace::diff_runs),
+/// [`Patch::runs`](crate::Patch::runs) and [`wire::decode`](crate::wire::decode).
+///
+/// # Arguments
+/// * `target`  - The array to apply the runs to.
+/// * `runs`    - The runs to apply.
+///
+/// # Example
+/// ```
+///     use diff_in_place::{apply_patch, PatchError};
+///     let mut state = [0, 0, 0, 0, 0, 0];
+///
+///     let runs: [(usize, &[u8]); 2] = [(1, &[1, 2]), (4, &[3])];
+///     apply_patch(&mut state, runs).unwrap();
+///     assert_eq!(state, [0, 1, 2, 0, 3, 0]);
+///
+///     let runs: [(usize, &[u8]); 2] = [(1, &[1, 2]), (5, &[3, 4])];
+///     assert_eq!(apply_patch(&mut state, runs), Err(PatchError::OutOfRange(5)));
+/// ```
+pub fn apply_patch<'r, T, I, const N: usize>(target: &mut [T; N], runs: I) -> Result<(), PatchError>
+where
+    T: Clone + 'r,
+    I: IntoIterator<Item = (usize, &'r [T])>,
+    I::IntoIter: Clone,
+{
+    let runs = runs.into_iter();
+    validate(N, runs.clone())?;
+    apply_validated(target, runs);
+    Ok(())
+}
+
+/// Validate the given runs, and apply them to an array only if its checksum matches.
+///
+/// This guards against applying a patch to a different array than the one it was made
+/// for, which would otherwise silently produce a mix of both.
+///
+/// # Arguments
+/// * `target`   - The array to apply the runs to.
+/// * `runs`     - The runs to apply.
+/// * `checksum` - The function computing the checksum of the array.
+/// * `expected` - The checksum of the array the runs were made for.
+///
+/// # Example
+/// ```
+///     use diff_in_place::{apply_patch_checked, PatchError};
+///     let mut state = [1u8, 2, 3, 4];
+///     let sum = |array: &[u8; 4]| array.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+///
+///     let runs: [(usize, &[u8]); 1] = [(0, &[5])];
+///     assert_eq!(
+///         apply_patch_checked(&mut state, runs, sum, 11),
+///         Err(PatchError::ChecksumMismatch)
+///     );
+///     apply_patch_checked(&mut state, runs, sum, 10).unwrap();
+///     assert_eq!(state, [5, 2, 3, 4]);
+/// ```
+pub fn apply_patch_checked<'r, T, I, C, K, const N: usize>(
+    target: &mut [T; N],
+    runs: I,
+    checksum: C,
+    expected: K,
+) -> Result<(), PatchError>
+where
+    T: Clone + 'r,
+    I: IntoIterator<Item = (usize, &'r [T])>,
+    I::IntoIter: Clone,
+    C: FnOnce(&[T; N]) -> K,
+    K: PartialEq,
+{
+    let runs = runs.into_iter();
+    validate(N, runs.clone())?;
+    if checksum(target) != expected {
+        return Err(PatchError::ChecksumMismatch);
+    }
+
+    apply_validated(target, runs);
+    Ok(())
+}
+
+/// Apply runs which were already validated.
+fn apply_validated<'r, T, I, const N: usize>(target: &mut [T; N], runs: I)
+where
+    T: Clone + 'r,
+    I: Iterator<Item = (usize, &'r [T])>,
+{
+    for (idx, diff) in runs {
+        target[idx..idx + diff.len()].clone_from_slice(diff);
+    }
+}
+
+fn validate<'r, T, I>(len: usize, runs: I) -> Result<(), PatchError>
+where
+    T: 'r,
+    I: Iterator<Item = (usize, &'r [T])>,
+{
+    let mut previous = 0..0;
+    for (idx, diff) in runs {
+        if idx < previous.start {
+            return Err(PatchError::Unordered(idx));
+        }
+        if idx < previous.end {
+            return Err(PatchError::Overlapping(idx));
+        }
+        match idx.checked_add(diff.len()) {
+            Some(end) if end <= len => previous = idx..end,
+            _ => return Err(PatchError::OutOfRange(idx)),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{wire, DiffInPlace, Patch};
+
+    #[test]
+    fn test_apply_patch_runs() {
+        let a = [0u8; 40];
+        let mut b = [0u8; 40];
+
+        b[..10].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+        b[20..25].copy_from_slice(&[11, 12, 13, 14, 15]);
+        b[39] = 20;
+
+        let mut c = a;
+        apply_patch(&mut c, a.diff_runs(&b)).unwrap();
+        assert_eq!(c, b);
+
+        let patch = Patch::<_, 40, 3, 16>::new(&b, &a).unwrap();
+        apply_patch(&mut c, patch.runs()).unwrap();
+        assert_eq!(c, a);
+
+        let mut buffer = [0u8; 32];
+        let len = wire::encode_into(&a, &b, &mut buffer).unwrap();
+        apply_patch(&mut c, wire::decode::<40>(&buffer[..len]).unwrap()).unwrap();
+        assert_eq!(c, b);
+    }
+
+    #[test]
+    fn test_apply_patch_rejects_invalid() {
+        let mut a = [0u8; 8];
+
+        let runs: [(usize, &[u8]); 2] = [(0, &[1, 2]), (7, &[3, 4])];
+        assert_eq!(apply_patch(&mut a, runs), Err(PatchError::OutOfRange(7)));
+
+        let runs: [(usize, &[u8]); 1] = [(usize::MAX, &[1])];
+        assert_eq!(
+            apply_patch(&mut a, runs),
+            Err(PatchError::OutOfRange(usize::MAX))
+        );
+
+        let runs: [(usize, &[u8]); 2] = [(4, &[1, 2]), (1, &[3])];
+        assert_eq!(apply_patch(&mut a, runs), Err(PatchError::Unordered(1)));
+
+        let runs: [(usize, &[u8]); 2] = [(4, &[1, 2]), (5, &[3])];
+        assert_eq!(apply_patch(&mut a, runs), Err(PatchError::Overlapping(5)));
+
+        // Nothing was applied
+        assert_eq!(a, [0u8; 8]);
+    }
+
+    #[test]
+    fn test_apply_patch_checked() {
+        let mut a = [1u8, 2, 3, 4];
+        let xor = |array: &[u8; 4]| array.iter().fold(0u8, |sum, byte| sum ^ byte);
+
+        let runs: [(usize, &[u8]); 1] = [(2, &[0, 0])];
+        assert_eq!(
+            apply_patch_checked(&mut a, runs, xor, 0),
+            Err(PatchError::ChecksumMismatch)
+        );
+        assert_eq!(a, [1, 2, 3, 4]);
+
+        apply_patch_checked(&mut a, runs, xor, 4).unwrap();
+        assert_eq!(a, [1, 2, 0, 0]);
+    }
+}
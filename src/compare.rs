@@ -0,0 +1,147 @@
+//! Comparators deciding whether elements count as changed when diffing.
+
+use core::ops::BitAnd;
+
+const MIN_CHUNK_LEN: usize = 16;
+const MAX_CHUNK_LEN: usize = 1024;
+
+/// Decides whether the elements at the same index of the two arrays are the same.
+///
+/// Runs of different elements are made of consecutive elements for which this returns
+/// `false`.
+pub trait Compare<T> {
+    /// Returns whether `left` and `right`, both found at `idx`, are the same.
+    fn same(&mut self, idx: usize, left: &T, right: &T) -> bool;
+
+    /// Returns how many of the leading elements of `left` and `right` are known to be the
+    /// same, so long stretches of them can be skipped without calling [`same`](Self::same)
+    /// for each.
+    ///
+    /// Returning fewer than are the same is always correct, and the default returns zero.
+    fn skip_same(&mut self, _left: &[T], _right: &[T]) -> usize {
+        0
+    }
+}
+
+/// Compares elements using their [`PartialEq`] implementation.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Equal;
+
+impl<T> Compare<T> for Equal
+where
+    T: PartialEq,
+{
+    fn same(&mut self, _idx: usize, left: &T, right: &T) -> bool {
+        left == right
+    }
+
+    fn skip_same(&mut self, left: &[T], right: &[T]) -> usize {
+        // Comparing whole chunks lets core compare plain integers with memcmp, the chunks
+        // start small so nearby runs waste little and grow over long equal stretches
+        let len = left.len().min(right.len());
+        let mut chunk_len = MIN_CHUNK_LEN;
+        let mut skipped = 0;
+        while skipped < len {
+            let end = len.min(skipped + chunk_len);
+            if left[skipped..end] != right[skipped..end] {
+                break;
+            }
+            skipped = end;
+            chunk_len = MAX_CHUNK_LEN.min(chunk_len * 2);
+        }
+        skipped
+    }
+}
+
+/// Compares elements using a custom equality function.
+///
+/// Created by [`DiffInPlace::diff_runs_by`](crate::DiffInPlace::diff_runs_by).
+#[derive(Copy, Clone, Debug)]
+pub struct By<E>(pub(crate) E);
+
+impl<T, E> Compare<T> for By<E>
+where
+    E: FnMut(&T, &T) -> bool,
+{
+    fn same(&mut self, _idx: usize, left: &T, right: &T) -> bool {
+        (self.0)(left, right)
+    }
+}
+
+/// Compares only the bits of elements selected by a per-index mask.
+///
+/// Created by [`DiffInPlace::diff_runs_masked`](crate::DiffInPlace::diff_runs_masked).
+#[derive(Copy, Clone, Debug)]
+pub struct Masked<'m, T>(pub(crate) &'m [T]);
+
+impl<T> Compare<T> for Masked<'_, T>
+where
+    T: Copy + PartialEq + BitAnd<Output = T>,
+{
+    fn same(&mut self, idx: usize, left: &T, right: &T) -> bool {
+        let mask = self.0[idx];
+        (*left & mask) == (*right & mask)
+    }
+
+    fn skip_same(&mut self, left: &[T], right: &[T]) -> usize {
+        // Equal elements are the same under any mask
+        Equal.skip_same(left, right)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::{DiffInPlace, DiffOptions};
+    use proptest::prelude::*;
+
+    const LEN: usize = 2048;
+
+    fn arrays() -> impl Strategy<Value = ([u8; LEN], [u8; LEN])> {
+        // Sparse changes, so there are long equal stretches to skip
+        let changes = proptest::collection::vec((0..LEN, any::<u8>()), 0..24);
+        (any::<u8>(), changes).prop_map(|(fill, changes)| {
+            let left = [fill; LEN];
+            let mut right = left;
+            for (idx, value) in changes {
+                right[idx] = value;
+            }
+            (left, right)
+        })
+    }
+
+    #[test]
+    fn test_equal_skip_same() {
+        let mut left = [0u32; 100];
+        let right = left;
+        assert_eq!(Equal.skip_same(&left, &right), 100);
+
+        left[37] = 1;
+        assert!(Equal.skip_same(&left, &right) <= 37);
+
+        // NaN is never the same, even against itself
+        let nan = [0.0f32, f32::NAN, 0.0];
+        assert_eq!(Equal.skip_same(&nan, &nan), 0);
+    }
+
+    proptest! {
+        #[test]
+        fn skipped_runs_equal_scalar_runs((left, right) in arrays(), max_gap in 0usize..8) {
+            let options = DiffOptions::new().max_gap(max_gap);
+            let mut scalar = left.diff_runs_by(&right, |x, y| x == y).with_options(options);
+            for run in left.diff_runs_with(&right, options) {
+                prop_assert_eq!(Some(run), scalar.next());
+            }
+            prop_assert_eq!(scalar.next(), None);
+
+            let mask = [0x0fu8; LEN];
+            let mut scalar = left.diff_runs_by(&right, |x, y| x & 0x0f == y & 0x0f);
+            for run in left.diff_runs_masked(&right, &mask) {
+                prop_assert_eq!(Some(run), scalar.next());
+            }
+            prop_assert_eq!(scalar.next(), None);
+        }
+    }
+}
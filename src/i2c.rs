@@ -0,0 +1,352 @@
+use embedded_hal::i2c::{I2c, Operation, SevenBitAddress};
+
+#[cfg(feature = "embedded-hal-async")]
+use embedded_hal_async::i2c::I2c as AsyncI2c;
+
+use crate::{DiffInPlace, DiffOptions};
+
+/// The width of the register pointer sent before the data of each write.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PointerWidth {
+    /// A single byte pointer, addressing up to 256 registers.
+    U8,
+    /// A two byte big-endian pointer, addressing up to 65536 registers.
+    U16,
+}
+
+impl PointerWidth {
+    /// Returns the highest register the pointer can address.
+    pub const fn max_register(self) -> usize {
+        match self {
+            PointerWidth::U8 => u8::MAX as usize,
+            PointerWidth::U16 => u16::MAX as usize,
+        }
+    }
+
+    fn encode(self, register: usize, buffer: &mut [u8; 2]) -> &[u8] {
+        match self {
+            PointerWidth::U8 => {
+                buffer[0] = register as u8;
+                &buffer[..1]
+            }
+            PointerWidth::U16 => {
+                *buffer = (register as u16).to_be_bytes();
+                &buffer[..]
+            }
+        }
+    }
+}
+
+/// Keeps the registers of an I2C device in sync with a desired state, writing only the
+/// registers which differ from what was last written.
+///
+/// Each run of different registers is sent as a single auto-incrementing write: the register
+/// pointer of the first register, followed by the values of the run.
+///
+/// # Example
+/// ```
+///     # use embedded_hal::i2c::{ErrorType, I2c, Operation};
+///     # struct Bus;
+///     # impl ErrorType for Bus { type Error = core::convert::Infallible; }
+///     # impl I2c for Bus {
+///     #     fn transaction(&mut self, _: u8, _: &mut [Operation<'_>]) -> Result<(), Self::Error> {
+///     #         Ok(())
+///     #     }
+///     # }
+///     # let i2c = Bus;
+///     use diff_in_place::{I2cRegisterSync, PointerWidth};
+///
+///     // The device comes out of reset with all registers cleared
+///     let mut device = I2cRegisterSync::new(i2c, 0x40, PointerWidth::U8, [0u8; 16]);
+///
+///     let mut config = [0u8; 16];
+///     config[4] = 0x80;
+///     config[5] = 0x01;
+///
+///     // Writes 0x80, 0x01 starting at register 4
+///     device.sync(&config).unwrap();
+///     assert_eq!(device.shadow(), &config);
+/// ```
+#[derive(Debug)]
+pub struct I2cRegisterSync<I2C, const N: usize> {
+    i2c: I2C,
+    address: SevenBitAddress,
+    pointer: PointerWidth,
+    shadow: [u8; N],
+}
+
+impl<I2C, const N: usize> I2cRegisterSync<I2C, N> {
+    /// Create a register sync for the device at the given address.
+    ///
+    /// # Arguments
+    /// * `i2c`     - The bus the device is on.
+    /// * `address` - The address of the device.
+    /// * `pointer` - The width of the register pointer of the device.
+    /// * `shadow`  - The current values of the registers of the device.
+    pub fn new(i2c: I2C, address: SevenBitAddress, pointer: PointerWidth, shadow: [u8; N]) -> Self {
+        assert!(
+            N.saturating_sub(1) <= pointer.max_register(),
+            "more registers than the pointer can address"
+        );
+        Self {
+            i2c,
+            address,
+            pointer,
+            shadow,
+        }
+    }
+
+    /// Returns the values of the registers as last written.
+    pub fn shadow(&self) -> &[u8; N] {
+        &self.shadow
+    }
+
+    /// Consumes the register sync, returning the bus.
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+}
+
+impl<I2C, const N: usize> I2cRegisterSync<I2C, N>
+where
+    I2C: I2c,
+{
+    /// Write the registers which differ from the desired state, one write per run.
+    ///
+    /// If a write fails, the registers of the runs written before it are kept as written, and
+    /// the error of the bus is returned.
+    ///
+    /// # Arguments
+    /// * `desired` - The desired values of the registers.
+    pub fn sync(&mut self, desired: &[u8; N]) -> Result<(), I2C::Error> {
+        self.sync_with(desired, DiffOptions::new())
+    }
+
+    /// Write the registers which differ from the desired state, with runs shaped by the given
+    /// options, one write per run.
+    ///
+    /// # Arguments
+    /// * `desired` - The desired values of the registers.
+    /// * `options` - The options controlling how runs are found.
+    pub fn sync_with(&mut self, desired: &[u8; N], options: DiffOptions) -> Result<(), I2C::Error> {
+        let Self {
+            i2c,
+            address,
+            pointer,
+            shadow,
+        } = self;
+
+        shadow.try_sync_from_with(desired, options, |idx, diff| {
+            let mut buffer = [0; 2];
+            let pointer = pointer.encode(idx, &mut buffer);
+
+            // Adjacent writes in a transaction are sent without a restart between them
+            i2c.transaction(
+                *address,
+                &mut [Operation::Write(pointer), Operation::Write(diff)],
+            )
+        })
+    }
+}
+
+#[cfg(feature = "embedded-hal-async")]
+impl<I2C, const N: usize> I2cRegisterSync<I2C, N>
+where
+    I2C: AsyncI2c,
+{
+    /// Async version of `sync` for buses implementing the `embedded-hal-async` traits.
+    /// Write the registers which differ from the desired state, one write per run.
+    ///
+    /// # Arguments
+    /// * `desired` - The desired values of the registers.
+    pub async fn sync_async(&mut self, desired: &[u8; N]) -> Result<(), I2C::Error> {
+        self.sync_with_async(desired, DiffOptions::new()).await
+    }
+
+    /// Async version of `sync_with` for buses implementing the `embedded-hal-async` traits.
+    /// Write the registers which differ from the desired state, with runs shaped by the given
+    /// options, one write per run.
+    ///
+    /// # Arguments
+    /// * `desired` - The desired values of the registers.
+    /// * `options` - The options controlling how runs are found.
+    pub async fn sync_with_async(
+        &mut self,
+        desired: &[u8; N],
+        options: DiffOptions<'_>,
+    ) -> Result<(), I2C::Error> {
+        let Self {
+            i2c,
+            address,
+            pointer,
+            shadow,
+        } = self;
+
+        shadow
+            .try_sync_from_with_async(desired, options, async |idx, diff| {
+                let mut buffer = [0; 2];
+                let pointer = pointer.encode(idx, &mut buffer);
+                i2c.transaction(
+                    *address,
+                    &mut [Operation::Write(pointer), Operation::Write(diff)],
+                )
+                .await
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal::i2c::{ErrorKind, ErrorType};
+
+    /// A bus recording the writes of each transaction, failing the transaction at `fail_at`.
+    struct MockI2c {
+        writes: [(u8, [u8; 8], usize); 4],
+        len: usize,
+        fail_at: Option<usize>,
+    }
+
+    impl MockI2c {
+        fn new() -> Self {
+            Self {
+                writes: [(0, [0; 8], 0); 4],
+                len: 0,
+                fail_at: None,
+            }
+        }
+
+        fn writes(&self) -> impl Iterator<Item = (u8, &[u8])> {
+            self.writes[..self.len]
+                .iter()
+                .map(|(address, data, len)| (*address, &data[..*len]))
+        }
+    }
+
+    impl ErrorType for MockI2c {
+        type Error = ErrorKind;
+    }
+
+    impl I2c for MockI2c {
+        fn transaction(
+            &mut self,
+            address: u8,
+            operations: &mut [Operation<'_>],
+        ) -> Result<(), Self::Error> {
+            if self.fail_at == Some(self.len) {
+                return Err(ErrorKind::Bus);
+            }
+
+            let (write_address, data, len) = &mut self.writes[self.len];
+            *write_address = address;
+            for operation in operations {
+                match operation {
+                    Operation::Write(bytes) => {
+                        data[*len..*len + bytes.len()].copy_from_slice(bytes);
+                        *len += bytes.len();
+                    }
+                    Operation::Read(_) => panic!("unexpected read"),
+                }
+            }
+            self.len += 1;
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "embedded-hal-async")]
+    impl AsyncI2c for MockI2c {
+        async fn transaction(
+            &mut self,
+            address: u8,
+            operations: &mut [Operation<'_>],
+        ) -> Result<(), Self::Error> {
+            I2c::transaction(self, address, operations)
+        }
+    }
+
+    #[test]
+    fn test_i2c_sync() {
+        let mut device = I2cRegisterSync::new(MockI2c::new(), 0x40, PointerWidth::U8, [0u8; 16]);
+
+        let mut desired = [0u8; 16];
+        desired[2..4].copy_from_slice(&[1, 2]);
+        desired[10] = 3;
+        device.sync(&desired).unwrap();
+        assert_eq!(device.shadow(), &desired);
+
+        // Nothing left to write
+        device.sync(&desired).unwrap();
+
+        const EXPECTED_WRITES: [(u8, &[u8]); 2] = [(0x40, &[2, 1, 2]), (0x40, &[10, 3])];
+
+        let i2c = device.release();
+        let mut write_idx = 0;
+        for (address, data) in i2c.writes() {
+            let (expected_address, expected_data) = EXPECTED_WRITES[write_idx];
+            assert_eq!(address, expected_address);
+            assert_eq!(data, expected_data);
+            write_idx += 1;
+        }
+        assert_eq!(write_idx, EXPECTED_WRITES.len());
+    }
+
+    #[test]
+    fn test_i2c_sync_wide_pointer() {
+        let mut device = I2cRegisterSync::new(MockI2c::new(), 0x50, PointerWidth::U16, [0u8; 300]);
+
+        let mut desired = [0u8; 300];
+        desired[0x123] = 4;
+        device.sync(&desired).unwrap();
+
+        let i2c = device.release();
+        let mut writes = i2c.writes();
+        assert_eq!(writes.next(), Some((0x50, &[0x01, 0x23, 4][..])));
+        assert_eq!(writes.next(), None);
+    }
+
+    #[test]
+    fn test_i2c_sync_error() {
+        let mut i2c = MockI2c::new();
+        i2c.fail_at = Some(1);
+        let mut device = I2cRegisterSync::new(i2c, 0x40, PointerWidth::U8, [0u8; 8]);
+
+        let desired = [0u8, 1, 0, 0, 0, 2, 0, 0];
+        assert_eq!(device.sync(&desired), Err(ErrorKind::Bus));
+        assert_eq!(device.shadow(), &[0, 1, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_i2c_too_many_registers() {
+        I2cRegisterSync::new(MockI2c::new(), 0x40, PointerWidth::U8, [0u8; 257]);
+    }
+
+    #[test]
+    fn test_i2c_pointer_limits() {
+        assert_eq!(PointerWidth::U8.max_register(), 0xff);
+        assert_eq!(PointerWidth::U16.max_register(), 0xffff);
+
+        // Every register of an 8-bit pointer can be addressed
+        I2cRegisterSync::new(MockI2c::new(), 0x40, PointerWidth::U8, [0u8; 256]);
+        I2cRegisterSync::new(MockI2c::new(), 0x40, PointerWidth::U8, [0u8; 0]);
+    }
+
+    #[test]
+    #[cfg(feature = "embedded-hal-async")]
+    fn test_i2c_sync_async() {
+        let mut i2c = MockI2c::new();
+        i2c.fail_at = Some(1);
+        let mut device = I2cRegisterSync::new(i2c, 0x40, PointerWidth::U8, [0u8; 8]);
+
+        let desired = [0u8, 1, 0, 0, 0, 2, 3, 0];
+        let result = crate::tests::block_on(device.sync_async(&desired));
+        assert_eq!(result, Err(ErrorKind::Bus));
+        assert_eq!(device.shadow(), &[0, 1, 0, 0, 0, 0, 0, 0]);
+
+        let i2c = device.release();
+        let mut writes = i2c.writes();
+        assert_eq!(writes.next(), Some((0x40, &[1, 1][..])));
+        assert_eq!(writes.next(), None);
+    }
+}
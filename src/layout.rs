@@ -0,0 +1,228 @@
+/// The order of the bytes of a register in a byte image.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Endian {
+    /// The most significant byte comes first.
+    Big,
+    /// The least significant byte comes first.
+    Little,
+}
+
+/// The width and byte order of the registers stored in a byte image.
+///
+/// Set with [`DiffOptions::registers`](crate::DiffOptions::registers), runs over the image
+/// always cover whole registers, while still being reported as bytes at byte offsets.
+///
+/// # Example
+/// ```
+///     use diff_in_place::{DiffInPlace, DiffOptions, Endian, RegisterLayout};
+///     const LAYOUT: RegisterLayout = RegisterLayout::new(2, Endian::Big);
+///
+///     let a = [0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+///     let b = [0x00, 0x00, 0x00, 0x12, 0x00, 0x00];
+///
+///     a.diff_in_place_with(&b, DiffOptions::new().registers(LAYOUT), |idx, diff| {
+///         // println!("{}: {:?} = {:#x}", idx, diff, LAYOUT.value(diff));
+///         // Prints:
+///         // 2: [0, 18] = 0x12
+///     });
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RegisterLayout {
+    width: usize,
+    endian: Endian,
+}
+
+impl RegisterLayout {
+    /// Create a layout of registers of `width` bytes, stored in the given byte order.
+    ///
+    /// # Panics
+    /// Panics if `width` is zero or more than 8.
+    pub const fn new(width: usize, endian: Endian) -> Self {
+        assert!(width > 0 && width <= 8, "width must be between 1 and 8");
+        Self { width, endian }
+    }
+
+    /// Returns the width of a register in bytes.
+    pub const fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns the byte order of a register.
+    pub const fn endian(&self) -> Endian {
+        self.endian
+    }
+
+    /// Returns the value of the register stored in the given bytes.
+    ///
+    /// # Panics
+    /// Panics if `bytes` is not exactly one register wide.
+    pub fn value(&self, bytes: &[u8]) -> u64 {
+        assert_eq!(bytes.len(), self.width, "bytes must be one register wide");
+        (0..self.width).fold(0, |value, byte| {
+            value | u64::from(bytes[byte]) << self.shift(byte)
+        })
+    }
+
+    /// Spread a mask per register over the bytes of an image, for comparing only the
+    /// selected bits of each register with
+    /// [`DiffInPlace::diff_runs_masked`](crate::DiffInPlace::diff_runs_masked).
+    ///
+    /// # Arguments
+    /// * `masks`   - The mask of every register in the image.
+    ///
+    /// # Panics
+    /// Panics if there are fewer masks than registers in the image.
+    ///
+    /// # Example
+    /// ```
+    ///     use diff_in_place::{DiffInPlace, DiffOptions, Endian, RegisterLayout};
+    ///     const LAYOUT: RegisterLayout = RegisterLayout::new(2, Endian::Little);
+    ///
+    ///     // Only the low nibble of each register matters
+    ///     let masks: [u8; 4] = LAYOUT.byte_masks(&[0x000f, 0x000f]);
+    ///     assert_eq!(masks, [0x0f, 0x00, 0x0f, 0x00]);
+    ///
+    ///     let a = [0x00, 0x00, 0x00, 0x00];
+    ///     let b = [0x10, 0xff, 0x01, 0x00];
+    ///
+    ///     let mut runs = a
+    ///         .diff_runs_masked(&b, &masks)
+    ///         .with_options(DiffOptions::new().registers(LAYOUT));
+    ///     assert_eq!(runs.next(), Some((2, &[0x01, 0x00][..])));
+    ///     assert_eq!(runs.next(), None);
+    /// ```
+    pub fn byte_masks<const N: usize>(&self, masks: &[u64]) -> [u8; N] {
+        core::array::from_fn(|idx| (masks[idx / self.width] >> self.shift(idx % self.width)) as u8)
+    }
+
+    /// Returns the shift of the byte at the given offset within a register.
+    fn shift(&self, byte: usize) -> usize {
+        match self.endian {
+            Endian::Big => (self.width - 1 - byte) * 8,
+            Endian::Little => byte * 8,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Access, DiffInPlace, DiffOptions, Region, RegionMap};
+
+    #[test]
+    fn test_layout_value() {
+        let bytes = [0x12, 0x34, 0x56, 0x78];
+        assert_eq!(
+            RegisterLayout::new(4, Endian::Big).value(&bytes),
+            0x12345678
+        );
+        assert_eq!(
+            RegisterLayout::new(4, Endian::Little).value(&bytes),
+            0x78563412
+        );
+        assert_eq!(
+            RegisterLayout::new(2, Endian::Big).value(&bytes[2..]),
+            0x5678
+        );
+    }
+
+    #[test]
+    fn test_layout_byte_masks() {
+        let masks = [0x0000_ff01, 0x8000_0000];
+        let big: [u8; 8] = RegisterLayout::new(4, Endian::Big).byte_masks(&masks);
+        assert_eq!(big, [0x00, 0x00, 0xff, 0x01, 0x80, 0x00, 0x00, 0x00]);
+        let little: [u8; 8] = RegisterLayout::new(4, Endian::Little).byte_masks(&masks);
+        assert_eq!(little, [0x01, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80]);
+    }
+
+    #[test]
+    fn test_registers_cover_whole_registers() {
+        let a = [0u8; 24];
+        let mut b = [0u8; 24];
+
+        b[1] = 1;
+        b[9] = 2;
+        b[10] = 3;
+        b[22] = 4;
+
+        // The gap between the first two registers is within reach once aligned
+        const EXPECTED_CALLS: [(usize, &[u8]); 2] = [
+            (0usize, &[0, 1, 0, 0, 0, 0, 0, 0, 0, 2, 3, 0]),
+            (20usize, &[0, 0, 4, 0]),
+        ];
+
+        let layout = RegisterLayout::new(4, Endian::Big);
+        let options = DiffOptions::new().registers(layout).max_gap(4);
+        let mut call_idx = 0;
+        a.diff_in_place_with(&b, options, |idx, diff| {
+            let (expected_idx, expected_diff) = EXPECTED_CALLS[call_idx];
+            assert_eq!(idx, expected_idx);
+            assert_eq!(diff, expected_diff);
+            assert_eq!(idx % layout.width(), 0);
+            assert_eq!(diff.len() % layout.width(), 0);
+            call_idx += 1;
+        });
+        assert_eq!(call_idx, EXPECTED_CALLS.len());
+    }
+
+    #[test]
+    fn test_registers_skip_unsafe_registers() {
+        const REGIONS: [Region; 3] = [
+            Region::new(2..4, Access::WriteOnly),
+            Region::new(4..5, Access::ReadOnly),
+            Region::new(6..7, Access::WriteOneToClear),
+        ];
+
+        let a = [0u8, 0, 0, 0, 0, 0, 1, 0, 1, 0];
+        let b = [0u8, 1, 2, 3, 0, 5, 1, 7, 0, 9];
+
+        // The register holding a read-only byte, and the one holding an unchanged
+        // write-one-to-clear byte, are dropped rather than written whole
+        const EXPECTED_CALLS: [(usize, &[u8]); 2] = [(0usize, &[0, 1, 2, 3]), (8usize, &[0, 9])];
+
+        let options = DiffOptions::new()
+            .registers(RegisterLayout::new(2, Endian::Big))
+            .regions(RegionMap::new(&REGIONS));
+        let mut call_idx = 0;
+        a.diff_in_place_with(&b, options, |idx, diff| {
+            let (expected_idx, expected_diff) = EXPECTED_CALLS[call_idx];
+            assert_eq!(idx, expected_idx);
+            assert_eq!(diff, expected_diff);
+            call_idx += 1;
+        });
+        assert_eq!(call_idx, EXPECTED_CALLS.len());
+
+        // A write-only register is only written if both of its bytes changed
+        let mut c = a;
+        c[3] = 3;
+        let mut runs = a.diff_runs_with(&c, options);
+        assert_eq!(runs.next(), None);
+    }
+
+    #[test]
+    fn test_registers_split_at_pages() {
+        let a = [0u8; 12];
+        let b = [1u8; 12];
+
+        let options = DiffOptions::new()
+            .registers(RegisterLayout::new(4, Endian::Big))
+            .page_size(8);
+        let mut runs = a.diff_runs_with(&b, options);
+        assert_eq!(runs.next(), Some((0, &[1; 8][..])));
+        assert_eq!(runs.next(), Some((8, &[1; 4][..])));
+        assert_eq!(runs.next(), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_registers_split_within_register() {
+        let a = [0u8; 12];
+        let b = [1u8; 12];
+
+        // A page boundary within a register
+        let options = DiffOptions::new()
+            .registers(RegisterLayout::new(4, Endian::Big))
+            .page_size(6);
+        a.diff_in_place_with(&b, options, |_idx, _diff| {});
+    }
+}
@@ -1,9 +1,82 @@
 #![no_std]
 
-pub trait DiffInPlace<T, const N: usize>
+pub mod compare;
+pub mod smbus;
+pub mod wire;
+
+use core::convert::Infallible;
+use core::future::Future;
+use core::ops::BitAnd;
+
+mod apply;
+#[cfg(feature = "embedded-hal")]
+mod i2c;
+mod layout;
+mod merge;
+mod options;
+mod patch;
+mod plan;
+mod region;
+mod runs;
+mod slice;
+#[cfg(feature = "embedded-hal")]
+mod spi;
+mod tracked;
+
+pub use apply::{apply_patch, apply_patch_checked, PatchError};
+#[cfg(feature = "embedded-hal")]
+pub use i2c::{I2cRegisterSync, PointerWidth};
+pub use layout::{Endian, RegisterLayout};
+pub use merge::{merge3, merge3_with, Merge, Resolution};
+pub use options::DiffOptions;
+pub use patch::{CapacityError, Patch, PatchRuns};
+pub use plan::{CostModel, LinearCost};
+pub use region::{Access, Region, RegionMap};
+pub use runs::DiffRuns;
+pub use slice::{DiffSlice, SliceDiff};
+pub use smbus::SmbusBlockWrite;
+#[cfg(feature = "embedded-hal")]
+pub use spi::{Framing, SpiRegisterSync};
+pub use tracked::Tracked;
+
+use compare::{By, Masked};
+use runs::Cursor;
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl<T, const N: usize> Sealed for [T; N] {}
+}
+
+/// In-place diffing of constant sized arrays.
+///
+/// This trait is sealed, and is only implemented for arrays. Its methods find runs with
+/// [`DiffRuns`] and update the array in place, which only the array implementation can do,
+/// so other implementations could not provide them.
+pub trait DiffInPlace<T, const N: usize>: sealed::Sealed
 where
     T: PartialEq,
 {
+    /// Perform a lazy in-place diff between two const-size arrays, returning an iterator
+    /// over each run of different elements, with the index into the array and
+    /// the slice of different elements from the other array.
+    ///
+    /// # Arguments
+    /// * `other`   - The other array to compare against.
+    ///
+    /// # Example
+    /// ```
+    ///     use diff_in_place::DiffInPlace;
+    ///     let a = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    ///     let b = [0, 0, 1, 2, 0, 0, 0, 3, 4, 5];
+    ///
+    ///     let mut runs = a.diff_runs(&b);
+    ///     assert_eq!(runs.next(), Some((2, &[1, 2][..])));
+    ///     assert_eq!(runs.next(), Some((7, &[3, 4, 5][..])));
+    ///     assert_eq!(runs.next(), None);
+    /// ```
+    fn diff_runs<'a>(&'a self, other: &'a [T; N]) -> DiffRuns<'a, T>;
+
     /// Fallible version of `diff_in_place` for propagating errors.
     /// Perform an in-place diff between two const-size arrays, invoking the given function
     /// for each run of different elements, with the index into the array and
@@ -28,9 +101,16 @@ where
     ///         Ok(())
     ///     }).unwrap();
     /// ```
-    fn try_diff_in_place<F, R>(&self, other: &[T; N], func: F) -> Result<(), R>
+    fn try_diff_in_place<F, R>(&self, other: &[T; N], mut func: F) -> Result<(), R>
     where
-        F: FnMut(usize, &[T]) -> Result<(), R>;
+        F: FnMut(usize, &[T]) -> Result<(), R>,
+    {
+        for (idx, diff) in self.diff_runs(other) {
+            func(idx, diff)?;
+        }
+
+        Ok(())
+    }
 
     /// Perform an in-place diff between two const-size arrays, invoking the given function
     /// for each run of different elements, with the index into the array and
@@ -57,197 +137,1357 @@ where
     where
         F: FnMut(usize, &[T]),
     {
-        self.try_diff_in_place(other, |idx, diff| -> Result<(), ()> {
+        self.try_diff_in_place(other, |idx, diff| -> Result<(), Infallible> {
             func(idx, diff);
             Ok(())
         })
-        .unwrap();
+        .unwrap_or_else(|never| match never {});
+    }
+
+    /// Perform a lazy in-place diff between two const-size arrays, returning an iterator
+    /// over each run of different elements as shaped by the given options.
+    ///
+    /// # Arguments
+    /// * `other`   - The other array to compare against.
+    /// * `options` - The options controlling how runs are reported.
+    ///
+    /// # Example
+    /// ```
+    ///     use diff_in_place::{DiffInPlace, DiffOptions};
+    ///     let a = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    ///     let b = [0, 0, 1, 0, 2, 0, 0, 0, 3, 0];
+    ///
+    ///     let mut runs = a.diff_runs_with(&b, DiffOptions::new().max_gap(1));
+    ///     assert_eq!(runs.next(), Some((2, &[1, 0, 2][..])));
+    ///     assert_eq!(runs.next(), Some((8, &[3][..])));
+    ///     assert_eq!(runs.next(), None);
+    /// ```
+    fn diff_runs_with<'a>(
+        &'a self,
+        other: &'a [T; N],
+        options: DiffOptions<'a>,
+    ) -> DiffRuns<'a, T> {
+        self.diff_runs(other).with_options(options)
+    }
+
+    /// Fallible version of `diff_in_place_with` for propagating errors.
+    /// Perform an in-place diff between two const-size arrays, invoking the given function
+    /// for each run of different elements as shaped by the given options.
+    ///
+    /// # Arguments
+    /// * `other`   - The other array to compare against.
+    /// * `options` - The options controlling how runs are reported.
+    /// * `func`    - The function to call for each run of different elements.
+    fn try_diff_in_place_with<F, R>(
+        &self,
+        other: &[T; N],
+        options: DiffOptions,
+        mut func: F,
+    ) -> Result<(), R>
+    where
+        F: FnMut(usize, &[T]) -> Result<(), R>,
+    {
+        for (idx, diff) in self.diff_runs_with(other, options) {
+            func(idx, diff)?;
+        }
+
+        Ok(())
+    }
+
+    /// Perform an in-place diff between two const-size arrays, invoking the given function
+    /// for each run of different elements as shaped by the given options.
+    ///
+    /// # Arguments
+    /// * `other`   - The other array to compare against.
+    /// * `options` - The options controlling how runs are reported.
+    /// * `func`    - The function to call for each run of different elements.
+    ///
+    /// # Example
+    /// ```
+    ///     use diff_in_place::{DiffInPlace, DiffOptions};
+    ///     let a = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    ///     let b = [0, 0, 1, 0, 2, 0, 0, 0, 3, 0];
+    ///
+    ///     a.diff_in_place_with(&b, DiffOptions::new().max_gap(1), |idx, diff| {
+    ///         // println!("{}: {:?}", idx, diff);
+    ///         // Prints:
+    ///         // 2: [1, 0, 2]
+    ///         // 8: [3]
+    ///     });
+    /// ```
+    fn diff_in_place_with<F>(&self, other: &[T; N], options: DiffOptions, mut func: F)
+    where
+        F: FnMut(usize, &[T]),
+    {
+        self.try_diff_in_place_with(other, options, |idx, diff| -> Result<(), Infallible> {
+            func(idx, diff);
+            Ok(())
+        })
+        .unwrap_or_else(|never| match never {});
+    }
+
+    /// Perform a lazy in-place diff between two const-size arrays, returning an iterator
+    /// over each run of different elements, where elements are the same if the given
+    /// equality function returns `true` for them.
+    ///
+    /// # Arguments
+    /// * `other`   - The other array to compare against.
+    /// * `eq`      - The function deciding whether two elements are the same.
+    ///
+    /// # Example
+    /// ```
+    ///     use diff_in_place::DiffInPlace;
+    ///     let a = [0.0f32, 0.0, 0.0, 0.0, 0.0];
+    ///     let b = [0.1, 0.0, 5.0, 0.0, -0.2];
+    ///
+    ///     let mut runs = a.diff_runs_by(&b, |x, y| (x - y).abs() < 0.5);
+    ///     assert_eq!(runs.next(), Some((2, &[5.0][..])));
+    ///     assert_eq!(runs.next(), None);
+    /// ```
+    fn diff_runs_by<'a, E>(&'a self, other: &'a [T; N], eq: E) -> DiffRuns<'a, T, By<E>>
+    where
+        E: FnMut(&T, &T) -> bool,
+    {
+        self.diff_runs(other).with_compare(By(eq))
+    }
+
+    /// Fallible version of `diff_in_place_by` for propagating errors.
+    /// Perform an in-place diff between two const-size arrays, invoking the given function
+    /// for each run of different elements, where elements are the same if the given
+    /// equality function returns `true` for them.
+    ///
+    /// # Arguments
+    /// * `other`   - The other array to compare against.
+    /// * `eq`      - The function deciding whether two elements are the same.
+    /// * `func`    - The function to call for each run of different elements.
+    fn try_diff_in_place_by<E, F, R>(&self, other: &[T; N], eq: E, mut func: F) -> Result<(), R>
+    where
+        E: FnMut(&T, &T) -> bool,
+        F: FnMut(usize, &[T]) -> Result<(), R>,
+    {
+        for (idx, diff) in self.diff_runs_by(other, eq) {
+            func(idx, diff)?;
+        }
+
+        Ok(())
+    }
+
+    /// Perform an in-place diff between two const-size arrays, invoking the given function
+    /// for each run of different elements, where elements are the same if the given
+    /// equality function returns `true` for them.
+    ///
+    /// # Arguments
+    /// * `other`   - The other array to compare against.
+    /// * `eq`      - The function deciding whether two elements are the same.
+    /// * `func`    - The function to call for each run of different elements.
+    ///
+    /// # Example
+    /// ```
+    ///     use diff_in_place::DiffInPlace;
+    ///     let a = [0.0f32, 0.0, 0.0, 0.0, 0.0, 0.0];
+    ///     let b = [0.1, 0.0, 5.0, 6.0, 0.0, -0.2];
+    ///
+    ///     a.diff_in_place_by(&b, |x, y| (x - y).abs() < 0.5, |idx, diff| {
+    ///         // println!("{}: {:?}", idx, diff);
+    ///         // Prints:
+    ///         // 2: [5.0, 6.0]
+    ///     });
+    /// ```
+    fn diff_in_place_by<E, F>(&self, other: &[T; N], eq: E, mut func: F)
+    where
+        E: FnMut(&T, &T) -> bool,
+        F: FnMut(usize, &[T]),
+    {
+        self.try_diff_in_place_by(other, eq, |idx, diff| -> Result<(), Infallible> {
+            func(idx, diff);
+            Ok(())
+        })
+        .unwrap_or_else(|never| match never {});
+    }
+
+    /// Fallible version of `diff_in_place_by_key` for propagating errors.
+    /// Perform an in-place diff between two const-size arrays, invoking the given function
+    /// for each run of different elements, where elements are the same if the keys
+    /// extracted from them are equal.
+    ///
+    /// # Arguments
+    /// * `other`   - The other array to compare against.
+    /// * `key`     - The function extracting the key to compare from an element.
+    /// * `func`    - The function to call for each run of different elements.
+    fn try_diff_in_place_by_key<K, KF, F, R>(
+        &self,
+        other: &[T; N],
+        mut key: KF,
+        func: F,
+    ) -> Result<(), R>
+    where
+        K: PartialEq,
+        KF: FnMut(&T) -> K,
+        F: FnMut(usize, &[T]) -> Result<(), R>,
+    {
+        self.try_diff_in_place_by(other, |left, right| key(left) == key(right), func)
+    }
+
+    /// Perform an in-place diff between two const-size arrays, invoking the given function
+    /// for each run of different elements, where elements are the same if the keys
+    /// extracted from them are equal.
+    ///
+    /// # Arguments
+    /// * `other`   - The other array to compare against.
+    /// * `key`     - The function extracting the key to compare from an element.
+    /// * `func`    - The function to call for each run of different elements.
+    ///
+    /// # Example
+    /// ```
+    ///     use diff_in_place::DiffInPlace;
+    ///     let a = [(1, 'a'), (2, 'b'), (3, 'c'), (4, 'd')];
+    ///     let b = [(1, 'x'), (5, 'b'), (3, 'c'), (4, 'y')];
+    ///
+    ///     a.diff_in_place_by_key(&b, |&(value, _)| value, |idx, diff| {
+    ///         // println!("{}: {:?}", idx, diff);
+    ///         // Prints:
+    ///         // 1: [(5, 'b')]
+    ///     });
+    /// ```
+    fn diff_in_place_by_key<K, KF, F>(&self, other: &[T; N], key: KF, mut func: F)
+    where
+        K: PartialEq,
+        KF: FnMut(&T) -> K,
+        F: FnMut(usize, &[T]),
+    {
+        self.try_diff_in_place_by_key(other, key, |idx, diff| -> Result<(), Infallible> {
+            func(idx, diff);
+            Ok(())
+        })
+        .unwrap_or_else(|never| match never {});
+    }
+
+    /// Perform a lazy in-place diff between two const-size arrays, returning an iterator
+    /// over each run of different elements, where only the bits set in the mask at the
+    /// same index are compared.
+    ///
+    /// The runs still hold the whole elements from the other array, so that writing them
+    /// preserves the bits which were not compared.
+    ///
+    /// # Arguments
+    /// * `other`   - The other array to compare against.
+    /// * `mask`    - The bits to compare at each index.
+    ///
+    /// # Example
+    /// ```
+    ///     use diff_in_place::DiffInPlace;
+    ///     let a = [0x00u8, 0x80, 0x00, 0x00];
+    ///     let b = [0x01u8, 0x00, 0x80, 0x00];
+    ///
+    ///     // The top bit of every register is a volatile status bit
+    ///     let mask = [0x7f; 4];
+    ///     let mut runs = a.diff_runs_masked(&b, &mask);
+    ///     assert_eq!(runs.next(), Some((0, &[0x01][..])));
+    ///     assert_eq!(runs.next(), None);
+    /// ```
+    fn diff_runs_masked<'a>(
+        &'a self,
+        other: &'a [T; N],
+        mask: &'a [T; N],
+    ) -> DiffRuns<'a, T, Masked<'a, T>>
+    where
+        T: Copy + BitAnd<Output = T>,
+    {
+        self.diff_runs(other).with_compare(Masked(mask))
+    }
+
+    /// Fallible version of `diff_in_place_masked` for propagating errors.
+    /// Perform an in-place diff between two const-size arrays, invoking the given function
+    /// for each run of different elements, where only the bits set in the mask at the
+    /// same index are compared.
+    ///
+    /// # Arguments
+    /// * `other`   - The other array to compare against.
+    /// * `mask`    - The bits to compare at each index.
+    /// * `func`    - The function to call for each run of different elements.
+    fn try_diff_in_place_masked<F, R>(
+        &self,
+        other: &[T; N],
+        mask: &[T; N],
+        mut func: F,
+    ) -> Result<(), R>
+    where
+        T: Copy + BitAnd<Output = T>,
+        F: FnMut(usize, &[T]) -> Result<(), R>,
+    {
+        for (idx, diff) in self.diff_runs_masked(other, mask) {
+            func(idx, diff)?;
+        }
+
+        Ok(())
+    }
+
+    /// Perform an in-place diff between two const-size arrays, invoking the given function
+    /// for each run of different elements, where only the bits set in the mask at the
+    /// same index are compared.
+    ///
+    /// # Arguments
+    /// * `other`   - The other array to compare against.
+    /// * `mask`    - The bits to compare at each index.
+    /// * `func`    - The function to call for each run of different elements.
+    ///
+    /// # Example
+    /// ```
+    ///     use diff_in_place::DiffInPlace;
+    ///     let a = [0x00u8, 0x80, 0x00, 0x00, 0x03];
+    ///     let b = [0x00u8, 0x01, 0x00, 0x00, 0x02];
+    ///
+    ///     // The top bit of every register is a volatile status bit,
+    ///     // and the last register has a reserved low bit
+    ///     let mask = [0x7f, 0x7f, 0x7f, 0x7f, 0x7e];
+    ///     a.diff_in_place_masked(&b, &mask, |idx, diff| {
+    ///         // println!("{}: {:x?}", idx, diff);
+    ///         // Prints:
+    ///         // 1: [1]
+    ///     });
+    /// ```
+    fn diff_in_place_masked<F>(&self, other: &[T; N], mask: &[T; N], mut func: F)
+    where
+        T: Copy + BitAnd<Output = T>,
+        F: FnMut(usize, &[T]),
+    {
+        self.try_diff_in_place_masked(other, mask, |idx, diff| -> Result<(), Infallible> {
+            func(idx, diff);
+            Ok(())
+        })
+        .unwrap_or_else(|never| match never {});
+    }
+
+    /// Fallible version of `diff_in_place_planned` for propagating errors.
+    /// Perform an in-place diff between two const-size arrays, invoking the given function
+    /// for each write in the cheapest set of writes covering all different elements,
+    /// according to the given cost model.
+    ///
+    /// Runs are merged whenever writing the equal elements between them is cheaper than
+    /// the overhead of another transaction, and a single write of the whole other array
+    /// is made instead if that is cheaper still.
+    ///
+    /// # Arguments
+    /// * `other`   - The other array to compare against.
+    /// * `model`   - The cost model of writes over the bus.
+    /// * `func`    - The function to call for each planned write.
+    fn try_diff_in_place_planned<M, F, R>(
+        &self,
+        other: &[T; N],
+        model: &M,
+        func: F,
+    ) -> Result<(), R>
+    where
+        M: CostModel,
+        F: FnMut(usize, &[T]) -> Result<(), R>,
+    {
+        plan::try_diff_in_place_planned(self, other, model, func)
+    }
+
+    /// Perform an in-place diff between two const-size arrays, invoking the given function
+    /// for each write in the cheapest set of writes covering all different elements,
+    /// according to the given cost model.
+    ///
+    /// # Arguments
+    /// * `other`   - The other array to compare against.
+    /// * `model`   - The cost model of writes over the bus.
+    /// * `func`    - The function to call for each planned write.
+    ///
+    /// # Example
+    /// ```
+    ///     use diff_in_place::{DiffInPlace, LinearCost};
+    ///     let a = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    ///     let b = [1, 0, 2, 0, 0, 0, 0, 0, 0, 3];
+    ///
+    ///     a.diff_in_place_planned(&b, &LinearCost::new(3, 1), |idx, diff| {
+    ///         // println!("{}: {:?}", idx, diff);
+    ///         // Prints:
+    ///         // 0: [1, 0, 2]
+    ///         // 9: [3]
+    ///     });
+    /// ```
+    fn diff_in_place_planned<M, F>(&self, other: &[T; N], model: &M, mut func: F)
+    where
+        M: CostModel,
+        F: FnMut(usize, &[T]),
+    {
+        self.try_diff_in_place_planned(other, model, |idx, diff| -> Result<(), Infallible> {
+            func(idx, diff);
+            Ok(())
+        })
+        .unwrap_or_else(|never| match never {});
+    }
+
+    /// Fallible version of `sync_from_with` for propagating errors.
+    /// Perform an in-place diff between two const-size arrays, invoking the given function
+    /// for each run of different elements as shaped by the given options, and copying each
+    /// run from the other array into this array once the function returns successfully.
+    ///
+    /// This keeps a shadow copy of a peripheral's state accurate, even if writing one of
+    /// the runs fails partway through: runs which were written are already copied, and
+    /// the failed run and any runs after it are left as they were.
+    ///
+    /// # Arguments
+    /// * `other`   - The other array to compare against and copy from.
+    /// * `options` - The options controlling how runs are reported.
+    /// * `func`    - The function to call for each run of different elements.
+    fn try_sync_from_with<F, R>(
+        &mut self,
+        other: &[T; N],
+        options: DiffOptions,
+        func: F,
+    ) -> Result<(), R>
+    where
+        T: Clone,
+        F: FnMut(usize, &[T]) -> Result<(), R>;
+
+    /// Fallible version of `sync_from` for propagating errors.
+    /// Perform an in-place diff between two const-size arrays, invoking the given function
+    /// for each run of different elements, and copying each run from the other array into
+    /// this array once the function returns successfully.
+    ///
+    /// # Arguments
+    /// * `other`   - The other array to compare against and copy from.
+    /// * `func`    - The function to call for each run of different elements.
+    ///
+    /// # Example
+    /// ```
+    ///     use diff_in_place::DiffInPlace;
+    ///     let mut shadow = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    ///     let desired = [0, 0, 1, 2, 0, 0, 0, 3, 4, 5];
+    ///
+    ///     let result = shadow.try_sync_from(&desired, |idx, diff| {
+    ///         // Writing the second run fails
+    ///         if idx == 7 {
+    ///             return Err(());
+    ///         }
+    ///         Ok(())
+    ///     });
+    ///
+    ///     assert_eq!(result, Err(()));
+    ///     assert_eq!(shadow, [0, 0, 1, 2, 0, 0, 0, 0, 0, 0]);
+    /// ```
+    fn try_sync_from<F, R>(&mut self, other: &[T; N], func: F) -> Result<(), R>
+    where
+        T: Clone,
+        F: FnMut(usize, &[T]) -> Result<(), R>,
+    {
+        self.try_sync_from_with(other, DiffOptions::new(), func)
+    }
+
+    /// Perform an in-place diff between two const-size arrays, invoking the given function
+    /// for each run of different elements as shaped by the given options, and copying each
+    /// run from the other array into this array.
+    ///
+    /// # Arguments
+    /// * `other`   - The other array to compare against and copy from.
+    /// * `options` - The options controlling how runs are reported.
+    /// * `func`    - The function to call for each run of different elements.
+    fn sync_from_with<F>(&mut self, other: &[T; N], options: DiffOptions, mut func: F)
+    where
+        T: Clone,
+        F: FnMut(usize, &[T]),
+    {
+        self.try_sync_from_with(other, options, |idx, diff| -> Result<(), Infallible> {
+            func(idx, diff);
+            Ok(())
+        })
+        .unwrap_or_else(|never| match never {});
+    }
+
+    /// Perform an in-place diff between two const-size arrays, invoking the given function
+    /// for each run of different elements, and copying each run from the other array into
+    /// this array.
+    ///
+    /// # Arguments
+    /// * `other`   - The other array to compare against and copy from.
+    /// * `func`    - The function to call for each run of different elements.
+    ///
+    /// # Example
+    /// ```
+    ///     use diff_in_place::DiffInPlace;
+    ///     let mut shadow = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    ///     let desired = [0, 0, 1, 2, 0, 0, 0, 3, 4, 5];
+    ///
+    ///     shadow.sync_from(&desired, |idx, diff| {
+    ///         // println!("{}: {:?}", idx, diff);
+    ///         // Prints:
+    ///         // 2: [1, 2]
+    ///         // 7: [3, 4, 5]
+    ///     });
+    ///
+    ///     assert_eq!(shadow, desired);
+    /// ```
+    fn sync_from<F>(&mut self, other: &[T; N], func: F)
+    where
+        T: Clone,
+        F: FnMut(usize, &[T]),
+    {
+        self.sync_from_with(other, DiffOptions::new(), func)
+    }
+
+    /// Async version of `try_diff_in_place` for awaiting inside the function.
+    /// Perform an in-place diff between two const-size arrays, invoking the given async
+    /// function for each run of different elements, and awaiting it before moving on to the
+    /// next run.
+    ///
+    /// # Arguments
+    /// * `other`   - The other array to compare against.
+    /// * `func`    - The async function to call for each run of different elements.
+    ///
+    /// # Example
+    /// ```
+    ///     # struct Bus;
+    ///     # impl Bus {
+    ///     #     async fn write(&mut self, _idx: usize, _data: &[u8]) -> Result<(), ()> { Ok(()) }
+    ///     # }
+    ///     # async fn example(bus: &mut Bus) -> Result<(), ()> {
+    ///     use diff_in_place::DiffInPlace;
+    ///     let a = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    ///     let b = [0, 0, 1, 2, 0, 0, 0, 3, 4, 5];
+    ///
+    ///     a.try_diff_in_place_async(&b, async |idx, diff| bus.write(idx, diff).await).await?;
+    ///     # Ok(())
+    ///     # }
+    /// ```
+    fn try_diff_in_place_async<F, R>(
+        &self,
+        other: &[T; N],
+        mut func: F,
+    ) -> impl Future<Output = Result<(), R>>
+    where
+        F: AsyncFnMut(usize, &[T]) -> Result<(), R>,
+    {
+        async move {
+            for (idx, diff) in self.diff_runs(other) {
+                func(idx, diff).await?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Async version of `try_sync_from_with` for awaiting inside the function.
+    /// Perform an in-place diff between two const-size arrays, invoking the given async
+    /// function for each run of different elements as shaped by the given options, and
+    /// copying each run from the other array into this array once the function succeeds.
+    ///
+    /// # Arguments
+    /// * `other`   - The other array to compare against and copy from.
+    /// * `options` - The options controlling how runs are reported.
+    /// * `func`    - The async function to call for each run of different elements.
+    fn try_sync_from_with_async<F, R>(
+        &mut self,
+        other: &[T; N],
+        options: DiffOptions,
+        func: F,
+    ) -> impl Future<Output = Result<(), R>>
+    where
+        T: Clone,
+        F: AsyncFnMut(usize, &[T]) -> Result<(), R>;
+
+    /// Async version of `try_sync_from` for awaiting inside the function.
+    /// Perform an in-place diff between two const-size arrays, invoking the given async
+    /// function for each run of different elements, and copying each run from the other
+    /// array into this array once the function succeeds.
+    ///
+    /// # Arguments
+    /// * `other`   - The other array to compare against and copy from.
+    /// * `func`    - The async function to call for each run of different elements.
+    ///
+    /// # Example
+    /// ```
+    ///     # struct Bus;
+    ///     # impl Bus {
+    ///     #     async fn write(&mut self, _idx: usize, _data: &[u8]) -> Result<(), ()> { Ok(()) }
+    ///     # }
+    ///     # async fn example(bus: &mut Bus) -> Result<(), ()> {
+    ///     use diff_in_place::DiffInPlace;
+    ///     let mut shadow = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    ///     let desired = [0, 0, 1, 2, 0, 0, 0, 3, 4, 5];
+    ///
+    ///     shadow.try_sync_from_async(&desired, async |idx, diff| bus.write(idx, diff).await).await?;
+    ///     assert_eq!(shadow, desired);
+    ///     # Ok(())
+    ///     # }
+    /// ```
+    fn try_sync_from_async<F, R>(
+        &mut self,
+        other: &[T; N],
+        func: F,
+    ) -> impl Future<Output = Result<(), R>>
+    where
+        T: Clone,
+        F: AsyncFnMut(usize, &[T]) -> Result<(), R>,
+    {
+        self.try_sync_from_with_async(other, DiffOptions::new(), func)
     }
 }
 
-#[derive(Copy, Clone)]
-enum DiffState {
-    Same,
-    Different(usize),
-}
+impl<T, const N: usize> DiffInPlace<T, N> for [T; N]
+where
+    T: PartialEq,
+{
+    fn diff_runs<'a>(&'a self, other: &'a [T; N]) -> DiffRuns<'a, T> {
+        DiffRuns::new(self, other)
+    }
+
+    fn try_sync_from_with<F, R>(
+        &mut self,
+        other: &[T; N],
+        options: DiffOptions,
+        mut func: F,
+    ) -> Result<(), R>
+    where
+        T: Clone,
+        F: FnMut(usize, &[T]) -> Result<(), R>,
+    {
+        let mut cursor = Cursor::new(options);
+        while let Some(run) = cursor.next_run(self, other) {
+            func(run.start, &other[run.clone()])?;
+
+            // Only copy the run once it has been handled successfully
+            self[run.clone()].clone_from_slice(&other[run]);
+        }
+
+        Ok(())
+    }
+
+    async fn try_sync_from_with_async<F, R>(
+        &mut self,
+        other: &[T; N],
+        options: DiffOptions<'_>,
+        mut func: F,
+    ) -> Result<(), R>
+    where
+        T: Clone,
+        F: AsyncFnMut(usize, &[T]) -> Result<(), R>,
+    {
+        let mut cursor = Cursor::new(options);
+        while let Some(run) = cursor.next_run(self, other) {
+            func(run.start, &other[run.clone()]).await?;
+
+            // Only copy the run once it has been handled successfully
+            self[run.clone()].clone_from_slice(&other[run]);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An element type which is neither `Copy` nor `Clone`
+    #[derive(Debug, PartialEq)]
+    enum Unique {
+        Empty,
+        Value(u32),
+    }
+
+    // An element type which is `Clone` but not `Copy`
+    #[derive(Clone, Debug, PartialEq)]
+    enum Shared {
+        Empty,
+        Value(u32),
+    }
+
+    #[test]
+    fn test_fully_same() {
+        let a = [0u8; 40];
+        let b = [0u8; 40];
+        a.diff_in_place(&b, |_, _| panic!("Should not be called"))
+    }
+
+    #[test]
+    fn test_fully_different() {
+        let a = [0u8; 40];
+        let b = [1u8; 40];
+
+        let mut called = false;
+        a.diff_in_place(&b, |idx, diff| {
+            assert_eq!(idx, 0);
+            assert_eq!(diff, &[1u8; 40]);
+            called = true;
+        });
+        assert!(called);
+    }
+
+    #[test]
+    fn test_start_different() {
+        let a = [0u8; 40];
+        let mut b = [0u8; 40];
+
+        b[..10].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+
+        const EXPECTED_CALLS: [(usize, &[u8]); 1] = [(0usize, &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10])];
+
+        let mut called = false;
+        a.diff_in_place(&b, |idx, diff| {
+            let (expected_idx, expected_diff) = EXPECTED_CALLS[0];
+            assert_eq!(idx, expected_idx);
+            assert_eq!(diff, expected_diff);
+            called = true;
+        });
+        assert!(called);
+    }
+
+    #[test]
+    fn test_middle_different() {
+        let a = [0u8; 40];
+        let mut b = [0u8; 40];
+
+        b[10..20].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+
+        const EXPECTED_CALLS: [(usize, &[u8]); 1] = [(10usize, &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10])];
+
+        let mut called = false;
+        a.diff_in_place(&b, |idx, diff| {
+            let (expected_idx, expected_diff) = EXPECTED_CALLS[0];
+            assert_eq!(idx, expected_idx);
+            assert_eq!(diff, expected_diff);
+            called = true;
+        });
+        assert!(called);
+    }
+
+    #[test]
+    fn test_end_different() {
+        let a = [0u8; 40];
+        let mut b = [0u8; 40];
+
+        b[30..].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+
+        const EXPECTED_CALLS: [(usize, &[u8]); 1] = [(30usize, &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10])];
+
+        let mut called = false;
+        a.diff_in_place(&b, |idx, diff| {
+            let (expected_idx, expected_diff) = EXPECTED_CALLS[0];
+            assert_eq!(idx, expected_idx);
+            assert_eq!(diff, expected_diff);
+            called = true;
+        });
+        assert!(called);
+    }
+
+    #[test]
+    fn test_multiple_different() {
+        let a = [0u8; 40];
+        let mut b = [0u8; 40];
+
+        b[..10].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+        b[20..25].copy_from_slice(&[11, 12, 13, 14, 15]);
+        b[39] = 20;
+
+        const EXPECTED_CALLS: [(usize, &[u8]); 3] = [
+            (0usize, &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]),
+            (20usize, &[11, 12, 13, 14, 15]),
+            (39usize, &[20]),
+        ];
+
+        let mut call_idx = 0;
+        let mut called = false;
+        a.diff_in_place(&b, |idx, diff| {
+            let (expected_idx, expected_diff) = EXPECTED_CALLS[call_idx];
+            assert_eq!(idx, expected_idx);
+            assert_eq!(diff, expected_diff);
+            call_idx += 1;
+            called = true;
+        });
+        assert!(called);
+    }
+
+    #[test]
+    fn test_other_types() {
+        let a = [0.0f32; 40];
+        let mut b = [0.0f32; 40];
+
+        b[..10].copy_from_slice(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7., 8., 9., 10.]);
+
+        const EXPECTED_CALLS: [(usize, &[f32]); 1] =
+            [(0usize, &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7., 8., 9., 10.])];
+
+        let mut called = false;
+        a.diff_in_place(&b, |idx, diff| {
+            let (expected_idx, expected_diff) = EXPECTED_CALLS[0];
+            assert_eq!(idx, expected_idx);
+            assert_eq!(diff, expected_diff);
+            called = true;
+        });
+        assert!(called);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_fallible_diff() {
+        let a = [0u8; 40];
+        let mut b = [0u8; 40];
+
+        b[..10].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+
+        a.try_diff_in_place(&b, |_idx, _diff| -> Result<(), ()> { Err(()) })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_diff_runs_fully_same() {
+        let a = [0u8; 40];
+        let b = [0u8; 40];
+        assert_eq!(a.diff_runs(&b).next(), None);
+    }
+
+    #[test]
+    fn test_diff_runs_multiple_different() {
+        let a = [0u8; 40];
+        let mut b = [0u8; 40];
+
+        b[..10].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+        b[20..25].copy_from_slice(&[11, 12, 13, 14, 15]);
+        b[39] = 20;
 
-impl<T, const N: usize> DiffInPlace<T, N> for [T; N]
-where
-    T: PartialEq + Copy,
-{
-    fn try_diff_in_place<F, R>(&self, other: &[T; N], mut func: F) -> Result<(), R>
-    where
-        F: FnMut(usize, &[T]) -> Result<(), R>,
-    {
-        // Go over the bytes in both arrays, comparing them.
-        // For all different runs within the two buffers, call the diff function
-        // with the index into the buffers and the bytes that need to be changed.
-        let byte_for_byte = self.iter().zip(other.iter());
-        let mut run_state = DiffState::Same;
-        for (current, (left, right)) in byte_for_byte.enumerate() {
-            match (run_state, left == right) {
-                (DiffState::Same, false) => {
-                    // We are starting an unequal run, preserve the current index
-                    run_state = DiffState::Different(current);
-                }
-                (DiffState::Different(run_start), true) => {
-                    // We are ending an unequal run, call the diff function
-                    func(run_start, &other[run_start..current])?;
-                    run_state = DiffState::Same;
-                }
-                _ => {
-                    // Run state is unchanged
-                }
-            }
-        }
+        const EXPECTED_CALLS: [(usize, &[u8]); 3] = [
+            (0usize, &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]),
+            (20usize, &[11, 12, 13, 14, 15]),
+            (39usize, &[20]),
+        ];
 
-        // If we are still in a different run, call the diff function
-        if let DiffState::Different(run_start) = run_state {
-            func(run_start, &other[run_start..])?;
+        let mut runs = a.diff_runs(&b);
+        for (expected_idx, expected_diff) in EXPECTED_CALLS {
+            assert_eq!(runs.next(), Some((expected_idx, expected_diff)));
         }
-
-        Ok(())
+        assert_eq!(runs.next(), None);
+        assert_eq!(runs.next(), None);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_diff_runs_matches_callback() {
+        let a = [0u8, 1, 2, 3, 4, 5, 6, 7, 8, 9];
+        let b = [1u8, 1, 0, 0, 4, 5, 0, 7, 8, 0];
+
+        let mut runs = a.diff_runs(&b);
+        a.diff_in_place(&b, |idx, diff| {
+            assert_eq!(runs.next(), Some((idx, diff)));
+        });
+        assert_eq!(runs.next(), None);
+    }
 
     #[test]
-    fn test_fully_same() {
+    fn test_max_gap_merges_close_runs() {
         let a = [0u8; 40];
-        let b = [0u8; 40];
-        a.diff_in_place(&b, |_, _| panic!("Should not be called"))
+        let mut b = [0u8; 40];
+
+        b[..3].copy_from_slice(&[1, 2, 3]);
+        b[5..7].copy_from_slice(&[4, 5]);
+        b[9] = 6;
+        b[20] = 7;
+
+        const EXPECTED_CALLS: [(usize, &[u8]); 2] =
+            [(0usize, &[1, 2, 3, 0, 0, 4, 5, 0, 0, 6]), (20usize, &[7])];
+
+        let mut call_idx = 0;
+        a.diff_in_place_with(&b, DiffOptions::new().max_gap(2), |idx, diff| {
+            let (expected_idx, expected_diff) = EXPECTED_CALLS[call_idx];
+            assert_eq!(idx, expected_idx);
+            assert_eq!(diff, expected_diff);
+            call_idx += 1;
+        });
+        assert_eq!(call_idx, EXPECTED_CALLS.len());
     }
 
     #[test]
-    fn test_fully_different() {
+    fn test_max_gap_keeps_distant_runs() {
         let a = [0u8; 40];
-        let b = [1u8; 40];
+        let mut b = [0u8; 40];
 
-        let mut called = false;
+        b[10] = 1;
+        b[14] = 2;
+        b[39] = 3;
+
+        const EXPECTED_CALLS: [(usize, &[u8]); 3] =
+            [(10usize, &[1]), (14usize, &[2]), (39usize, &[3])];
+
+        let mut runs = a.diff_runs_with(&b, DiffOptions::new().max_gap(2));
+        for (expected_idx, expected_diff) in EXPECTED_CALLS {
+            assert_eq!(runs.next(), Some((expected_idx, expected_diff)));
+        }
+        assert_eq!(runs.next(), None);
+    }
+
+    #[test]
+    fn test_max_gap_zero_is_default() {
+        let a = [0u8, 1, 2, 3, 4, 5, 6, 7, 8, 9];
+        let b = [1u8, 1, 0, 0, 4, 5, 0, 7, 8, 0];
+
+        let mut runs = a.diff_runs_with(&b, DiffOptions::new());
         a.diff_in_place(&b, |idx, diff| {
-            assert_eq!(idx, 0);
-            assert_eq!(diff, &[1u8; 40]);
+            assert_eq!(runs.next(), Some((idx, diff)));
+        });
+        assert_eq!(runs.next(), None);
+    }
+
+    #[test]
+    fn test_max_gap_larger_than_array() {
+        let a = [0u8; 10];
+        let mut b = [0u8; 10];
+
+        b[1] = 1;
+        b[8] = 2;
+
+        let mut called = false;
+        a.diff_in_place_with(&b, DiffOptions::new().max_gap(usize::MAX), |idx, diff| {
+            assert_eq!(idx, 1);
+            assert_eq!(diff, &[1, 0, 0, 0, 0, 0, 0, 2]);
             called = true;
         });
         assert!(called);
     }
 
     #[test]
-    fn test_start_different() {
+    fn test_max_run_len_splits_long_runs() {
         let a = [0u8; 40];
         let mut b = [0u8; 40];
 
         b[..10].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+        b[20..22].copy_from_slice(&[11, 12]);
 
-        const EXPECTED_CALLS: [(usize, &[u8]); 1] = [(0usize, &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10])];
+        const EXPECTED_CALLS: [(usize, &[u8]); 4] = [
+            (0usize, &[1, 2, 3, 4]),
+            (4usize, &[5, 6, 7, 8]),
+            (8usize, &[9, 10]),
+            (20usize, &[11, 12]),
+        ];
 
-        let mut called = false;
-        a.diff_in_place(&b, |idx, diff| {
-            let (expected_idx, expected_diff) = EXPECTED_CALLS[0];
+        let mut call_idx = 0;
+        a.diff_in_place_with(&b, DiffOptions::new().max_run_len(4), |idx, diff| {
+            let (expected_idx, expected_diff) = EXPECTED_CALLS[call_idx];
             assert_eq!(idx, expected_idx);
             assert_eq!(diff, expected_diff);
-            called = true;
+            call_idx += 1;
         });
-        assert!(called);
+        assert_eq!(call_idx, EXPECTED_CALLS.len());
     }
 
     #[test]
-    fn test_middle_different() {
+    fn test_max_run_len_fully_different() {
         let a = [0u8; 40];
-        let mut b = [0u8; 40];
+        let b = [1u8; 40];
 
-        b[10..20].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+        let mut runs = a.diff_runs_with(&b, DiffOptions::new().max_run_len(32));
+        assert_eq!(runs.next(), Some((0, &[1u8; 32][..])));
+        assert_eq!(runs.next(), Some((32, &[1u8; 8][..])));
+        assert_eq!(runs.next(), None);
+    }
 
-        const EXPECTED_CALLS: [(usize, &[u8]); 1] = [(10usize, &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10])];
+    #[test]
+    fn test_max_run_len_trims_merged_gaps() {
+        let a = [0u8; 20];
+        let mut b = [0u8; 20];
 
-        let mut called = false;
-        a.diff_in_place(&b, |idx, diff| {
-            let (expected_idx, expected_diff) = EXPECTED_CALLS[0];
+        b[0] = 1;
+        b[1] = 2;
+        b[6] = 3;
+
+        const EXPECTED_CALLS: [(usize, &[u8]); 2] = [(0usize, &[1, 2]), (6usize, &[3])];
+
+        let options = DiffOptions::new().max_gap(5).max_run_len(3);
+        let mut call_idx = 0;
+        a.diff_in_place_with(&b, options, |idx, diff| {
+            let (expected_idx, expected_diff) = EXPECTED_CALLS[call_idx];
             assert_eq!(idx, expected_idx);
             assert_eq!(diff, expected_diff);
-            called = true;
+            call_idx += 1;
         });
-        assert!(called);
+        assert_eq!(call_idx, EXPECTED_CALLS.len());
     }
 
     #[test]
-    fn test_end_different() {
-        let a = [0u8; 40];
-        let mut b = [0u8; 40];
+    #[should_panic]
+    fn test_max_run_len_zero() {
+        let _ = DiffOptions::new().max_run_len(0);
+    }
 
-        b[30..].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+    #[test]
+    fn test_page_size_splits_across_boundaries() {
+        let a = [0u8; 32];
+        let mut b = [0u8; 32];
 
-        const EXPECTED_CALLS: [(usize, &[u8]); 1] = [(30usize, &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10])];
+        b[6..10].copy_from_slice(&[1, 2, 3, 4]);
+        b[16..26].copy_from_slice(&[5, 6, 7, 8, 9, 10, 11, 12, 13, 14]);
 
-        let mut called = false;
-        a.diff_in_place(&b, |idx, diff| {
-            let (expected_idx, expected_diff) = EXPECTED_CALLS[0];
+        const EXPECTED_CALLS: [(usize, &[u8]); 4] = [
+            (6usize, &[1, 2]),
+            (8usize, &[3, 4]),
+            (16usize, &[5, 6, 7, 8, 9, 10, 11, 12]),
+            (24usize, &[13, 14]),
+        ];
+
+        let mut call_idx = 0;
+        a.diff_in_place_with(&b, DiffOptions::new().page_size(8), |idx, diff| {
+            let (expected_idx, expected_diff) = EXPECTED_CALLS[call_idx];
             assert_eq!(idx, expected_idx);
             assert_eq!(diff, expected_diff);
-            called = true;
+            call_idx += 1;
         });
-        assert!(called);
+        assert_eq!(call_idx, EXPECTED_CALLS.len());
     }
 
     #[test]
-    fn test_multiple_different() {
-        let a = [0u8; 40];
+    fn test_page_size_trims_merged_gaps() {
+        let a = [0u8; 32];
+        let mut b = [0u8; 32];
+
+        b[5] = 1;
+        b[10] = 2;
+
+        const EXPECTED_CALLS: [(usize, &[u8]); 2] = [(5usize, &[1]), (10usize, &[2])];
+
+        let options = DiffOptions::new().max_gap(8).page_size(8);
+        let mut call_idx = 0;
+        a.diff_in_place_with(&b, options, |idx, diff| {
+            let (expected_idx, expected_diff) = EXPECTED_CALLS[call_idx];
+            assert_eq!(idx, expected_idx);
+            assert_eq!(diff, expected_diff);
+            call_idx += 1;
+        });
+        assert_eq!(call_idx, EXPECTED_CALLS.len());
+    }
+
+    #[test]
+    fn test_align_widens_runs() {
+        let a = [0u8; 10];
+        let mut b = [0u8; 10];
+
+        b[2] = 1;
+        b[9] = 2;
+
+        const EXPECTED_CALLS: [(usize, &[u8]); 2] = [(0usize, &[0, 0, 1, 0]), (8usize, &[0, 2])];
+
+        let mut call_idx = 0;
+        a.diff_in_place_with(&b, DiffOptions::new().align(4), |idx, diff| {
+            let (expected_idx, expected_diff) = EXPECTED_CALLS[call_idx];
+            assert_eq!(idx, expected_idx);
+            assert_eq!(diff, expected_diff);
+            call_idx += 1;
+        });
+        assert_eq!(call_idx, EXPECTED_CALLS.len());
+    }
+
+    #[test]
+    fn test_align_with_pages_and_gaps() {
+        let a = [0u8; 32];
+        let mut b = [0u8; 32];
+
+        b[1] = 1;
+        b[9] = 2;
+        b[13] = 3;
+        b[17] = 4;
+
+        const EXPECTED_CALLS: [(usize, &[u8]); 3] = [
+            (0usize, &[0, 1]),
+            (8usize, &[0, 2, 0, 0, 0, 3]),
+            (16usize, &[0, 4]),
+        ];
+
+        let options = DiffOptions::new().align(2).max_gap(4).page_size(8);
+        let mut call_idx = 0;
+        a.diff_in_place_with(&b, options, |idx, diff| {
+            let (expected_idx, expected_diff) = EXPECTED_CALLS[call_idx];
+            assert_eq!(idx, expected_idx);
+            assert_eq!(diff, expected_diff);
+            call_idx += 1;
+        });
+        assert_eq!(call_idx, EXPECTED_CALLS.len());
+    }
+
+    #[test]
+    fn test_align_merges_adjacent_units() {
+        let a = [0u8; 16];
+        let mut b = [0u8; 16];
+
+        b[3] = 1;
+        b[6] = 2;
+
+        let mut runs = a.diff_runs_with(&b, DiffOptions::new().align(4));
+        assert_eq!(runs.next(), Some((0, &[0, 0, 0, 1, 0, 0, 2, 0][..])));
+        assert_eq!(runs.next(), None);
+    }
+
+    #[test]
+    fn test_sync_from() {
+        let mut a = [0u8; 40];
+        let mut b = [0u8; 40];
+
+        b[..10].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+        b[20..25].copy_from_slice(&[11, 12, 13, 14, 15]);
+
+        let mut calls = 0;
+        a.sync_from(&b, |_, _| calls += 1);
+        assert_eq!(calls, 2);
+        assert_eq!(a, b);
+
+        a.sync_from(&b, |_, _| panic!("Should not be called"));
+    }
+
+    #[test]
+    fn test_fallible_sync_from_keeps_failed_runs() {
+        let mut a = [0u8; 40];
         let mut b = [0u8; 40];
 
         b[..10].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
         b[20..25].copy_from_slice(&[11, 12, 13, 14, 15]);
         b[39] = 20;
 
-        const EXPECTED_CALLS: [(usize, &[u8]); 3] = [
-            (0usize, &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]),
-            (20usize, &[11, 12, 13, 14, 15]),
-            (39usize, &[20]),
+        let result = a.try_sync_from(&b, |idx, _diff| if idx == 20 { Err(idx) } else { Ok(()) });
+        assert_eq!(result, Err(20));
+
+        let mut expected = [0u8; 40];
+        expected[..10].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+        assert_eq!(a, expected);
+
+        // Retrying picks up from the failed run
+        const EXPECTED_CALLS: [(usize, &[u8]); 2] =
+            [(20usize, &[11, 12, 13, 14, 15]), (39usize, &[20])];
+
+        let mut call_idx = 0;
+        a.sync_from(&b, |idx, diff| {
+            let (expected_idx, expected_diff) = EXPECTED_CALLS[call_idx];
+            assert_eq!(idx, expected_idx);
+            assert_eq!(diff, expected_diff);
+            call_idx += 1;
+        });
+        assert_eq!(call_idx, EXPECTED_CALLS.len());
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_sync_from_with_options() {
+        let mut a = [0u8; 16];
+        let mut b = [0u8; 16];
+
+        b[6..10].copy_from_slice(&[1, 2, 3, 4]);
+
+        let mut writes = 0;
+        let result = a.try_sync_from_with(&b, DiffOptions::new().page_size(8), |idx, _diff| {
+            writes += 1;
+            if idx == 8 {
+                Err(())
+            } else {
+                Ok(())
+            }
+        });
+        assert_eq!(result, Err(()));
+        assert_eq!(writes, 2);
+        assert_eq!(a[..8], b[..8]);
+        assert_eq!(a[8..], [0u8; 8]);
+    }
+
+    #[test]
+    fn test_non_copy_types() {
+        let a = [
+            Unique::Empty,
+            Unique::Value(1),
+            Unique::Value(2),
+            Unique::Empty,
+        ];
+        let b = [
+            Unique::Value(0),
+            Unique::Value(1),
+            Unique::Value(3),
+            Unique::Value(4),
+        ];
+
+        const EXPECTED_CALLS: [(usize, &[Unique]); 2] = [
+            (0usize, &[Unique::Value(0)]),
+            (2usize, &[Unique::Value(3), Unique::Value(4)]),
         ];
 
         let mut call_idx = 0;
-        let mut called = false;
         a.diff_in_place(&b, |idx, diff| {
             let (expected_idx, expected_diff) = EXPECTED_CALLS[call_idx];
             assert_eq!(idx, expected_idx);
             assert_eq!(diff, expected_diff);
             call_idx += 1;
+        });
+        assert_eq!(call_idx, EXPECTED_CALLS.len());
+    }
+
+    #[test]
+    fn test_sync_from_clone_types() {
+        let mut a = [Shared::Empty, Shared::Empty, Shared::Value(1)];
+        let b = [Shared::Empty, Shared::Value(2), Shared::Empty];
+
+        let mut called = false;
+        a.sync_from(&b, |idx, diff| {
+            assert_eq!(idx, 1);
+            assert_eq!(diff, &[Shared::Value(2), Shared::Empty]);
             called = true;
         });
         assert!(called);
+        assert_eq!(a, b);
     }
 
     #[test]
-    fn test_other_types() {
+    fn test_diff_by_tolerance() {
         let a = [0.0f32; 40];
         let mut b = [0.0f32; 40];
 
-        b[..10].copy_from_slice(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7., 8., 9., 10.]);
+        b[..10].copy_from_slice(&[0.01, 0.02, 3.0, 4.0, 0.0, 0.0, -0.01, 8., 0.0, 0.0]);
 
-        const EXPECTED_CALLS: [(usize, &[f32]); 1] =
-            [(0usize, &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7., 8., 9., 10.])];
+        const EXPECTED_CALLS: [(usize, &[f32]); 2] = [(2usize, &[3.0, 4.0]), (7usize, &[8.])];
+
+        let mut call_idx = 0;
+        a.diff_in_place_by(
+            &b,
+            |x, y| (x - y).abs() < 0.1,
+            |idx, diff| {
+                let (expected_idx, expected_diff) = EXPECTED_CALLS[call_idx];
+                assert_eq!(idx, expected_idx);
+                assert_eq!(diff, expected_diff);
+                call_idx += 1;
+            },
+        );
+        assert_eq!(call_idx, EXPECTED_CALLS.len());
+    }
+
+    #[test]
+    fn test_diff_by_key() {
+        let a = [(0u8, 0u8); 10];
+        let mut b = [(0u8, 0u8); 10];
+
+        b[1] = (0, 1);
+        b[4] = (1, 1);
+        b[5] = (2, 0);
 
         let mut called = false;
-        a.diff_in_place(&b, |idx, diff| {
-            let (expected_idx, expected_diff) = EXPECTED_CALLS[0];
+        a.diff_in_place_by_key(
+            &b,
+            |&(value, _)| value,
+            |idx, diff| {
+                assert_eq!(idx, 4);
+                assert_eq!(diff, &[(1, 1), (2, 0)]);
+                called = true;
+            },
+        );
+        assert!(called);
+    }
+
+    #[test]
+    fn test_diff_runs_by_with_options() {
+        let a = [0u8; 10];
+        let b = [1u8, 2, 0, 3, 0, 0, 0, 4, 9, 9];
+
+        let mut runs = a
+            .diff_runs_by(&b, |x, y| x.abs_diff(*y) < 2)
+            .with_options(DiffOptions::new().max_gap(1));
+        assert_eq!(runs.next(), Some((1, &[2, 0, 3][..])));
+        assert_eq!(runs.next(), Some((7, &[4, 9, 9][..])));
+        assert_eq!(runs.next(), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_fallible_diff_by() {
+        let a = [0u8; 40];
+        let b = [1u8; 40];
+
+        a.try_diff_in_place_by(
+            &b,
+            |x, y| x == y,
+            |_idx, _diff| -> Result<(), ()> { Err(()) },
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_masked_ignores_unmasked_bits() {
+        let a = [0x80u8; 40];
+        let mut b = [0x00u8; 40];
+
+        b[10..12].copy_from_slice(&[0x01, 0x82]);
+        b[30] = 0x7f;
+
+        const EXPECTED_CALLS: [(usize, &[u8]); 2] = [(10usize, &[0x01, 0x82]), (30usize, &[0x7f])];
+
+        let mask = [0x7fu8; 40];
+        let mut call_idx = 0;
+        a.diff_in_place_masked(&b, &mask, |idx, diff| {
+            let (expected_idx, expected_diff) = EXPECTED_CALLS[call_idx];
             assert_eq!(idx, expected_idx);
             assert_eq!(diff, expected_diff);
-            called = true;
+            call_idx += 1;
         });
-        assert!(called);
+        assert_eq!(call_idx, EXPECTED_CALLS.len());
     }
 
     #[test]
-    #[should_panic]
-    fn test_fallible_diff() {
+    fn test_masked_per_index() {
+        let a = [0x0000u16, 0x0000, 0x0000, 0x0000];
+        let b = [0xff00u16, 0x00ff, 0xffff, 0x0001];
+        let mask = [0x00ff, 0xff00, 0x0000, 0xffff];
+
+        let mut runs = a.diff_runs_masked(&b, &mask);
+        assert_eq!(runs.next(), Some((3, &[0x0001][..])));
+        assert_eq!(runs.next(), None);
+    }
+
+    /// Poll a future to completion, for futures which never wait on anything.
+    pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = core::pin::pin!(future);
+        let mut context = core::task::Context::from_waker(core::task::Waker::noop());
+        loop {
+            if let core::task::Poll::Ready(output) = future.as_mut().poll(&mut context) {
+                return output;
+            }
+        }
+    }
+
+    #[test]
+    fn test_diff_in_place_async() {
         let a = [0u8; 40];
         let mut b = [0u8; 40];
 
         b[..10].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+        b[20..25].copy_from_slice(&[11, 12, 13, 14, 15]);
+        b[39] = 20;
 
-        a.try_diff_in_place(&b, |_idx, _diff| -> Result<(), ()> { Err(()) })
-            .unwrap();
+        // The function borrows its captures mutably across awaits
+        let mut call_idx = 0;
+        let mut runs = a.diff_runs(&b);
+        let result: Result<(), ()> = block_on(a.try_diff_in_place_async(&b, async |idx, diff| {
+            assert_eq!(runs.next(), Some((idx, diff)));
+            core::future::ready(()).await;
+            call_idx += 1;
+            Ok(())
+        }));
+        assert_eq!(result, Ok(()));
+        assert_eq!(call_idx, 3);
+        assert_eq!(runs.next(), None);
+    }
+
+    #[test]
+    fn test_sync_from_async() {
+        let mut shadow = [0u8; 10];
+        let desired = [0u8, 0, 1, 2, 0, 0, 0, 3, 4, 5];
+
+        let result = block_on(shadow.try_sync_from_async(&desired, async |idx, _diff| {
+            // Writing the second run fails
+            if idx == 7 {
+                return Err(());
+            }
+            Ok(())
+        }));
+        assert_eq!(result, Err(()));
+        assert_eq!(shadow, [0, 0, 1, 2, 0, 0, 0, 0, 0, 0]);
+
+        let options = DiffOptions::new().max_gap(1);
+        let result: Result<(), ()> =
+            block_on(
+                shadow.try_sync_from_with_async(&desired, options, async |idx, diff| {
+                    assert_eq!((idx, diff), (7, &[3, 4, 5][..]));
+                    Ok(())
+                }),
+            );
+        assert_eq!(result, Ok(()));
+        assert_eq!(shadow, desired);
     }
 }
@@ -0,0 +1,332 @@
+use core::iter::Peekable;
+use core::ops::Range;
+
+use crate::{CapacityError, DiffInPlace, DiffRuns};
+
+/// Which side wins when both sides change the same elements differently.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Resolution {
+    /// Keep the elements of `ours`.
+    Ours,
+    /// Keep the elements of `theirs`.
+    Theirs,
+}
+
+/// The result of a three-way merge, holding up to `MAX_CONFLICTS` conflicting ranges.
+///
+/// Created by [`merge3`] and [`merge3_with`].
+#[derive(Clone, Debug)]
+pub struct Merge<T, const N: usize, const MAX_CONFLICTS: usize> {
+    merged: [T; N],
+    conflicts: [Range<usize>; MAX_CONFLICTS],
+    len: usize,
+}
+
+impl<T, const N: usize, const MAX_CONFLICTS: usize> Merge<T, N, MAX_CONFLICTS> {
+    /// Returns the merged array.
+    pub fn merged(&self) -> &[T; N] {
+        &self.merged
+    }
+
+    /// Consumes the merge, returning the merged array.
+    pub fn into_merged(self) -> [T; N] {
+        self.merged
+    }
+
+    /// Returns the ranges of elements which both sides changed differently, in order.
+    pub fn conflicts(&self) -> &[Range<usize>] {
+        &self.conflicts[..self.len]
+    }
+
+    /// Returns whether any elements were changed differently by both sides.
+    pub fn has_conflicts(&self) -> bool {
+        self.len != 0
+    }
+}
+
+/// Merge the changes two sides made to copies of a common base array.
+///
+/// Changes made by only one side, or made identically by both, are taken as-is, element by
+/// element. Where both sides changed the same elements to different values, those elements
+/// are a conflict, resolved by taking either side.
+///
+/// # Arguments
+/// * `base`        - The array both sides started from.
+/// * `ours`        - The array with our changes.
+/// * `theirs`      - The array with their changes.
+/// * `resolution`  - Which side to take for conflicting ranges.
+///
+/// # Example
+/// ```
+///     use diff_in_place::{merge3, Merge, Resolution};
+///     let base = [0, 0, 0, 0, 0, 0, 0, 0];
+///     let ours = [1, 1, 0, 0, 2, 2, 0, 0];
+///     let theirs = [0, 0, 0, 4, 0, 3, 3, 0];
+///
+///     let merge: Merge<_, 8, 2> = merge3(&base, &ours, &theirs, Resolution::Ours).unwrap();
+///     assert_eq!(merge.merged(), &[1, 1, 0, 4, 2, 2, 3, 0]);
+///     assert_eq!(merge.conflicts(), &[5..6]);
+/// ```
+pub fn merge3<T, const N: usize, const MAX_CONFLICTS: usize>(
+    base: &[T; N],
+    ours: &[T; N],
+    theirs: &[T; N],
+    resolution: Resolution,
+) -> Result<Merge<T, N, MAX_CONFLICTS>, CapacityError>
+where
+    T: PartialEq + Clone,
+{
+    merge3_with(
+        base,
+        ours,
+        theirs,
+        |_idx, ours, theirs, merged| match resolution {
+            Resolution::Ours => merged.clone_from_slice(ours),
+            Resolution::Theirs => merged.clone_from_slice(theirs),
+        },
+    )
+}
+
+/// Merge the changes two sides made to copies of a common base array, resolving conflicts
+/// with the given function.
+///
+/// The function is called for each conflicting range with its index, the elements of both
+/// sides, and the elements of the merged array, which initially hold those of the base.
+///
+/// # Arguments
+/// * `base`    - The array both sides started from.
+/// * `ours`    - The array with our changes.
+/// * `theirs`  - The array with their changes.
+/// * `resolve` - The function to call for each conflicting range.
+///
+/// # Example
+/// ```
+///     use diff_in_place::{merge3_with, Merge};
+///     let base = [0u8, 0, 0, 0];
+///     let ours = [0u8, 0b01, 0, 0];
+///     let theirs = [0u8, 0b10, 0, 1];
+///
+///     let merge: Merge<_, 4, 1> = merge3_with(&base, &ours, &theirs, |_idx, ours, theirs, merged| {
+///         for ((merged, ours), theirs) in merged.iter_mut().zip(ours).zip(theirs) {
+///             *merged = ours | theirs;
+///         }
+///     })
+///     .unwrap();
+///     assert_eq!(merge.merged(), &[0, 0b11, 0, 1]);
+/// ```
+pub fn merge3_with<T, F, const N: usize, const MAX_CONFLICTS: usize>(
+    base: &[T; N],
+    ours: &[T; N],
+    theirs: &[T; N],
+    mut resolve: F,
+) -> Result<Merge<T, N, MAX_CONFLICTS>, CapacityError>
+where
+    T: PartialEq + Clone,
+    F: FnMut(usize, &[T], &[T], &mut [T]),
+{
+    let mut merge = Merge {
+        merged: base.clone(),
+        conflicts: core::array::from_fn(|_| 0..0),
+        len: 0,
+    };
+
+    let mut our_runs = base.diff_runs(ours).peekable();
+    let mut their_runs = base.diff_runs(theirs).peekable();
+
+    loop {
+        let ours_first = match (our_runs.peek(), their_runs.peek()) {
+            (Some(&(our_idx, _)), Some(&(their_idx, _))) => our_idx <= their_idx,
+            (ours, _) => ours.is_some(),
+        };
+        let mut changed_by_ours = ours_first;
+        let mut changed_by_theirs = !ours_first;
+        let first = if ours_first {
+            &mut our_runs
+        } else {
+            &mut their_runs
+        };
+        let Some(mut range) = next_run(first, usize::MAX) else {
+            break;
+        };
+
+        // Grow the range over every run of either side overlapping it
+        loop {
+            if let Some(run) = next_run(&mut our_runs, range.end) {
+                changed_by_ours = true;
+                range.end = range.end.max(run.end);
+            } else if let Some(run) = next_run(&mut their_runs, range.end) {
+                changed_by_theirs = true;
+                range.end = range.end.max(run.end);
+            } else {
+                break;
+            }
+        }
+
+        if !changed_by_theirs || ours[range.clone()] == theirs[range.clone()] {
+            merge.merged[range.clone()].clone_from_slice(&ours[range]);
+            continue;
+        } else if !changed_by_ours {
+            merge.merged[range.clone()].clone_from_slice(&theirs[range]);
+            continue;
+        }
+
+        // Both sides changed elements of the range, so take whichever side changed each
+        // element, and only resolve the elements both sides changed differently
+        let mut conflict_start = None;
+        for idx in range.clone() {
+            let our_change = ours[idx] != base[idx];
+            let their_change = theirs[idx] != base[idx];
+            let conflicting = our_change && their_change && ours[idx] != theirs[idx];
+            match (conflict_start, conflicting) {
+                (None, true) => conflict_start = Some(idx),
+                (Some(start), false) => {
+                    merge.resolve(start..idx, ours, theirs, &mut resolve)?;
+                    conflict_start = None;
+                }
+                _ => {}
+            }
+
+            if our_change && !conflicting {
+                merge.merged[idx] = ours[idx].clone();
+            } else if their_change && !conflicting {
+                merge.merged[idx] = theirs[idx].clone();
+            }
+        }
+        if let Some(start) = conflict_start {
+            merge.resolve(start..range.end, ours, theirs, &mut resolve)?;
+        }
+    }
+
+    Ok(merge)
+}
+
+impl<T, const N: usize, const MAX_CONFLICTS: usize> Merge<T, N, MAX_CONFLICTS> {
+    /// Record the given range as a conflict, and resolve it with the given function.
+    fn resolve<F>(
+        &mut self,
+        range: Range<usize>,
+        ours: &[T; N],
+        theirs: &[T; N],
+        resolve: &mut F,
+    ) -> Result<(), CapacityError>
+    where
+        F: FnMut(usize, &[T], &[T], &mut [T]),
+    {
+        let conflict = self.conflicts.get_mut(self.len).ok_or(CapacityError)?;
+        *conflict = range.clone();
+        self.len += 1;
+        resolve(
+            range.start,
+            &ours[range.clone()],
+            &theirs[range.clone()],
+            &mut self.merged[range],
+        );
+        Ok(())
+    }
+}
+
+/// Returns the next run if it starts before `end`.
+fn next_run<T>(runs: &mut Peekable<DiffRuns<'_, T>>, end: usize) -> Option<Range<usize>>
+where
+    T: PartialEq,
+{
+    runs.next_if(|&(idx, _)| idx < end)
+        .map(|(idx, diff)| idx..idx + diff.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge3_disjoint() {
+        let base = [0u8; 16];
+        let mut ours = base;
+        let mut theirs = base;
+
+        ours[1..4].copy_from_slice(&[1, 2, 3]);
+        theirs[4..6].copy_from_slice(&[4, 5]);
+        ours[10] = 6;
+        theirs[10] = 6;
+        theirs[15] = 7;
+
+        let merge: Merge<_, 16, 0> = merge3(&base, &ours, &theirs, Resolution::Ours).unwrap();
+        assert!(!merge.has_conflicts());
+        assert_eq!(
+            merge.into_merged(),
+            [0, 1, 2, 3, 4, 5, 0, 0, 0, 0, 6, 0, 0, 0, 0, 7]
+        );
+    }
+
+    #[test]
+    fn test_merge3_resolution() {
+        let base = [0u8; 12];
+        let ours = [1u8, 1, 1, 0, 0, 2, 0, 0, 0, 0, 3, 0];
+        let theirs = [0u8, 0, 4, 4, 4, 4, 4, 0, 0, 0, 5, 5];
+
+        let merge: Merge<_, 12, 3> = merge3(&base, &ours, &theirs, Resolution::Ours).unwrap();
+        assert_eq!(merge.conflicts(), &[2..3, 5..6, 10..11]);
+        assert_eq!(merge.merged(), &[1, 1, 1, 4, 4, 2, 4, 0, 0, 0, 3, 5]);
+
+        let merge: Merge<_, 12, 3> = merge3(&base, &ours, &theirs, Resolution::Theirs).unwrap();
+        assert_eq!(merge.conflicts(), &[2..3, 5..6, 10..11]);
+        assert_eq!(merge.merged(), &[1, 1, 4, 4, 4, 4, 4, 0, 0, 0, 5, 5]);
+
+        assert_eq!(
+            merge3::<_, 12, 2>(&base, &ours, &theirs, Resolution::Ours).err(),
+            Some(CapacityError)
+        );
+    }
+
+    #[test]
+    fn test_merge3_partial_overlap() {
+        let base = [0u8; 8];
+        let ours = [1u8, 1, 1, 0, 0, 0, 0, 0];
+        let theirs = [0u8, 0, 2, 2, 2, 2, 2, 2];
+
+        // Only the element changed by both sides conflicts, the rest of each side is kept
+        let merge: Merge<_, 8, 1> = merge3(&base, &ours, &theirs, Resolution::Ours).unwrap();
+        assert_eq!(merge.conflicts().len(), 1);
+        assert_eq!(merge.conflicts()[0], 2..3);
+        assert_eq!(merge.merged(), &[1, 1, 1, 2, 2, 2, 2, 2]);
+
+        let merge: Merge<_, 8, 1> = merge3(&base, &ours, &theirs, Resolution::Theirs).unwrap();
+        assert_eq!(merge.conflicts().len(), 1);
+        assert_eq!(merge.conflicts()[0], 2..3);
+        assert_eq!(merge.merged(), &[1, 1, 2, 2, 2, 2, 2, 2]);
+
+        // Elements changed identically by both sides split the conflicts
+        let ours = [0u8, 1, 3, 1, 1, 0, 0, 0];
+        let theirs = [0u8, 2, 3, 2, 0, 0, 4, 0];
+        let merge: Merge<_, 8, 2> = merge3(&base, &ours, &theirs, Resolution::Theirs).unwrap();
+        assert_eq!(merge.conflicts(), &[1..2, 3..4]);
+        assert_eq!(merge.merged(), &[0, 2, 3, 2, 1, 0, 4, 0]);
+    }
+
+    #[test]
+    fn test_merge3_with_callback() {
+        let base = [0u8; 10];
+        let mut ours = base;
+        let mut theirs = base;
+
+        ours[2..5].copy_from_slice(&[1, 2, 3]);
+        theirs[4..6].copy_from_slice(&[4, 5]);
+
+        const EXPECTED_CALLS: [(usize, &[u8], &[u8]); 1] = [(4usize, &[3], &[4])];
+
+        let mut call_idx = 0;
+        let merge: Merge<_, 10, 1> =
+            merge3_with(&base, &ours, &theirs, |idx, ours, theirs, merged| {
+                let (expected_idx, expected_ours, expected_theirs) = EXPECTED_CALLS[call_idx];
+                assert_eq!(idx, expected_idx);
+                assert_eq!(ours, expected_ours);
+                assert_eq!(theirs, expected_theirs);
+                assert_eq!(merged, &[0]);
+                merged.fill(9);
+                call_idx += 1;
+            })
+            .unwrap();
+        assert_eq!(call_idx, EXPECTED_CALLS.len());
+        assert_eq!(merge.merged(), &[0, 0, 1, 2, 9, 5, 0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,133 @@
+use crate::{RegionMap, RegisterLayout};
+
+/// Options controlling how runs of different elements are reported.
+///
+/// The default options report every maximal run of different elements as-is,
+/// which is what [`DiffInPlace::try_diff_in_place`](crate::DiffInPlace::try_diff_in_place) does.
+///
+/// # Example
+/// ```
+///     use diff_in_place::{DiffInPlace, DiffOptions};
+///     let a = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+///     let b = [0, 0, 1, 0, 2, 0, 0, 0, 3, 0];
+///
+///     let options = DiffOptions::new().max_gap(1);
+///     a.diff_in_place_with(&b, options, |idx, diff| {
+///         // println!("{}: {:?}", idx, diff);
+///         // Prints:
+///         // 2: [1, 0, 2]
+///         // 8: [3]
+///     });
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DiffOptions<'r> {
+    pub(crate) max_gap: usize,
+    pub(crate) max_run_len: usize,
+    pub(crate) page_size: usize,
+    pub(crate) align: usize,
+    pub(crate) whole_units: bool,
+    pub(crate) regions: Option<RegionMap<'r>>,
+}
+
+impl<'r> DiffOptions<'r> {
+    /// Create the default options, reporting every run of different elements as-is.
+    pub const fn new() -> Self {
+        Self {
+            max_gap: 0,
+            max_run_len: usize::MAX,
+            page_size: usize::MAX,
+            align: 1,
+            whole_units: false,
+            regions: None,
+        }
+    }
+
+    /// Merge runs separated by `max_gap` or fewer equal elements into a single run.
+    ///
+    /// The merged run covers the whole span in the other array, including the equal
+    /// elements in between. This trades a few redundant elements for fewer runs, which is
+    /// cheaper on buses where every write carries a fixed overhead.
+    pub const fn max_gap(mut self, max_gap: usize) -> Self {
+        self.max_gap = max_gap;
+        self
+    }
+
+    /// Split runs longer than `max_run_len` elements into consecutive chunks.
+    ///
+    /// Each chunk is reported with its own starting index, and never starts or ends with
+    /// equal elements merged in by [`DiffOptions::max_gap`]. This is useful for buses and
+    /// peripherals which limit the size of a single transfer.
+    ///
+    /// When combined with [`DiffOptions::align`], the length is rounded down to a multiple
+    /// of the alignment, but never below a single aligned unit.
+    ///
+    /// # Panics
+    /// Panics if `max_run_len` is zero.
+    pub const fn max_run_len(mut self, max_run_len: usize) -> Self {
+        assert!(max_run_len > 0, "max_run_len must be non-zero");
+        self.max_run_len = max_run_len;
+        self
+    }
+
+    /// Split runs so that none crosses a multiple of `page_size`.
+    ///
+    /// This is required for memories which wrap around within a page on writes, such as
+    /// the 24Cxx family of EEPROMs, where a write crossing a page boundary corrupts the
+    /// start of the page. Like with [`DiffOptions::max_run_len`], the split runs never
+    /// start or end with merged in equal elements.
+    ///
+    /// # Panics
+    /// Panics if `page_size` is zero. Diffing with these options panics if `page_size` is
+    /// not a multiple of [`DiffOptions::align`], as runs would then end within a unit.
+    pub const fn page_size(mut self, page_size: usize) -> Self {
+        assert!(page_size > 0, "page_size must be non-zero");
+        self.page_size = page_size;
+        self
+    }
+
+    /// Widen runs so that they start at a multiple of `align` and span a multiple of
+    /// `align` elements.
+    ///
+    /// The widened runs cover the aligned span in the other array, including any equal
+    /// elements. A run reaching the end of the array is cut short there if the array
+    /// length is not itself a multiple of `align`.
+    ///
+    /// # Panics
+    /// Panics if `align` is zero.
+    pub const fn align(mut self, align: usize) -> Self {
+        assert!(align > 0, "align must be non-zero");
+        self.align = align;
+        self
+    }
+
+    /// Diff a byte image of registers with the given layout, so that runs always start and
+    /// end on register boundaries.
+    ///
+    /// This is [`DiffOptions::align`] to the width of a register, except that a register is
+    /// always written whole, and never cut short next to addresses [`DiffOptions::regions`]
+    /// forbids rewriting. Instead, a register holding an address which is not writable, or
+    /// one which is not safe to rewrite and has not changed, is dropped. Gaps between
+    /// registers are still only merged across addresses which are safe to rewrite.
+    pub const fn registers(mut self, layout: RegisterLayout) -> Self {
+        self.align = layout.width();
+        self.whole_units = true;
+        self
+    }
+
+    /// Restrict runs to the writable addresses of a register file.
+    ///
+    /// Different elements at addresses which are not writable are dropped, and runs are only
+    /// merged by [`DiffOptions::max_gap`] or widened by [`DiffOptions::align`] across addresses
+    /// which are safe to rewrite. Widening is skipped where it would not be safe, so runs next
+    /// to such addresses may not be aligned.
+    pub const fn regions(mut self, regions: RegionMap<'r>) -> Self {
+        self.regions = Some(regions);
+        self
+    }
+}
+
+impl Default for DiffOptions<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
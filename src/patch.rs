@@ -0,0 +1,458 @@
+use core::fmt;
+use core::iter::FusedIterator;
+
+use crate::{DiffInPlace, DiffOptions};
+
+/// The error returned when a patch needs more runs or data than it has capacity for.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CapacityError;
+
+impl fmt::Display for CapacityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("too many runs or elements for the patch capacity")
+    }
+}
+
+/// An owned diff between two const-size arrays, holding up to `MAX_RUNS` runs with up to
+/// `MAX_DATA` elements between them.
+///
+/// Unlike the runs passed to the callback of
+/// [`DiffInPlace::try_diff_in_place`](crate::DiffInPlace::try_diff_in_place), a patch can be
+/// kept around, applied later, and inverted to undo it. It needs no allocation: each run is
+/// kept as its index and length, with the data of all runs packed one after the other into
+/// an array of `MAX_DATA` elements, so a patch is only as large as its capacity.
+///
+/// # Example
+/// ```
+///     use diff_in_place::Patch;
+///     let base = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+///     let target = [0, 0, 1, 2, 0, 0, 0, 3, 4, 5];
+///
+///     let patch = Patch::<_, 10, 4, 8>::new(&base, &target).unwrap();
+///     let undo = patch.invert(&base);
+///
+///     let mut state = base;
+///     patch.apply(&mut state);
+///     assert_eq!(state, target);
+///
+///     undo.apply(&mut state);
+///     assert_eq!(state, base);
+/// ```
+#[derive(Clone)]
+pub struct Patch<T, const N: usize, const MAX_RUNS: usize, const MAX_DATA: usize> {
+    runs: [(usize, usize); MAX_RUNS],
+    len: usize,
+    data: [T; MAX_DATA],
+    data_len: usize,
+}
+
+impl<T, const N: usize, const MAX_RUNS: usize, const MAX_DATA: usize>
+    Patch<T, N, MAX_RUNS, MAX_DATA>
+where
+    T: PartialEq + Clone + Default,
+{
+    /// Create a patch turning `base` into `target`.
+    ///
+    /// # Arguments
+    /// * `base`    - The array the patch applies to.
+    /// * `target`  - The array applying the patch results in.
+    pub fn new(base: &[T; N], target: &[T; N]) -> Result<Self, CapacityError> {
+        Self::new_with(base, target, DiffOptions::new())
+    }
+
+    /// Create a patch turning `base` into `target`, with runs shaped by the given options.
+    ///
+    /// # Arguments
+    /// * `base`    - The array the patch applies to.
+    /// * `target`  - The array applying the patch results in.
+    /// * `options` - The options controlling how runs are found.
+    pub fn new_with(
+        base: &[T; N],
+        target: &[T; N],
+        options: DiffOptions,
+    ) -> Result<Self, CapacityError> {
+        let mut patch = Self::empty();
+        base.try_diff_in_place_with(target, options, |idx, diff| {
+            let data = patch.reserve(idx, diff.len())?;
+            data.clone_from_slice(diff);
+            Ok(())
+        })?;
+
+        Ok(patch)
+    }
+}
+
+impl<T, const N: usize, const MAX_RUNS: usize, const MAX_DATA: usize>
+    Patch<T, N, MAX_RUNS, MAX_DATA>
+{
+    /// Returns the number of runs in the patch.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the patch has no runs, and so changes nothing.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of elements set by the runs of the patch.
+    pub fn data_len(&self) -> usize {
+        self.data_len
+    }
+
+    /// Returns an iterator over the runs of the patch, with the index of each run and the
+    /// elements it sets.
+    pub fn runs(&self) -> PatchRuns<'_, T> {
+        PatchRuns {
+            runs: self.runs[..self.len].iter(),
+            data: &self.data[..self.data_len],
+        }
+    }
+
+    /// Apply the patch to the given array.
+    ///
+    /// # Arguments
+    /// * `target`  - The array to apply the patch to.
+    pub fn apply(&self, target: &mut [T; N])
+    where
+        T: Clone,
+    {
+        for (idx, diff) in self.runs() {
+            target[idx..idx + diff.len()].clone_from_slice(diff);
+        }
+    }
+
+    /// Compose the patch with a later one into a single patch, with the effect of applying
+    /// both in sequence.
+    ///
+    /// Where both patches set the same elements, the later one wins. Overlapping and
+    /// adjacent runs are joined, so the composed patch has as few runs as possible.
+    ///
+    /// # Arguments
+    /// * `later`   - The patch applied after this one.
+    ///
+    /// # Example
+    /// ```
+    ///     use diff_in_place::Patch;
+    ///     let a = [0, 0, 0, 0, 0, 0, 0, 0];
+    ///     let b = [0, 1, 1, 0, 0, 0, 2, 0];
+    ///     let c = [0, 1, 3, 3, 0, 0, 0, 0];
+    ///
+    ///     let first = Patch::<_, 8, 2, 4>::new(&a, &b).unwrap();
+    ///     let second = Patch::<_, 8, 2, 4>::new(&b, &c).unwrap();
+    ///     let both = first.compose(&second).unwrap();
+    ///     assert_eq!(both.len(), 2);
+    ///
+    ///     let mut state = a;
+    ///     both.apply(&mut state);
+    ///     assert_eq!(state, c);
+    /// ```
+    pub fn compose(&self, later: &Self) -> Result<Self, CapacityError>
+    where
+        T: Clone + Default,
+    {
+        let mut spans = [(0, 0); MAX_RUNS];
+        let mut len = 0;
+        let mut earlier_runs = self.runs().peekable();
+        let mut later_runs = later.runs().peekable();
+
+        // Join the runs of both patches into spans, each of them covered by runs entirely
+        loop {
+            let next = match (earlier_runs.peek(), later_runs.peek()) {
+                (Some(earlier), Some(later)) if earlier.0 <= later.0 => earlier_runs.next(),
+                (Some(_), None) => earlier_runs.next(),
+                _ => later_runs.next(),
+            };
+            let Some((start, diff)) = next else {
+                break;
+            };
+            let end = start + diff.len();
+
+            match spans[..len].last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => {
+                    *spans.get_mut(len).ok_or(CapacityError)? = (start, end);
+                    len += 1;
+                }
+            }
+        }
+
+        let mut composed = Self::empty();
+        for &(start, end) in &spans[..len] {
+            composed.reserve(start, end - start)?;
+        }
+
+        // Fill in the data of the earlier patch, then overwrite it with that of the later one
+        for patch in [self, later] {
+            let mut spans = composed.runs[..composed.len].iter();
+            let (mut start, mut span_len) = (0, 0);
+            let mut offset = 0;
+            for (idx, diff) in patch.runs() {
+                while idx >= start + span_len {
+                    offset += span_len;
+                    (start, span_len) = *spans.next().expect("every run is within a span");
+                }
+
+                let data_start = offset + idx - start;
+                composed.data[data_start..data_start + diff.len()].clone_from_slice(diff);
+            }
+        }
+
+        Ok(composed)
+    }
+
+    /// Create the inverse of the patch, which undoes it when applied after it.
+    ///
+    /// # Arguments
+    /// * `base`    - The array the patch applies to.
+    pub fn invert(&self, base: &[T; N]) -> Self
+    where
+        T: Clone,
+    {
+        let mut inverse = self.clone();
+        let mut offset = 0;
+        for &(idx, len) in &self.runs[..self.len] {
+            inverse.data[offset..offset + len].clone_from_slice(&base[idx..idx + len]);
+            offset += len;
+        }
+
+        inverse
+    }
+
+    /// Create a patch with no runs.
+    fn empty() -> Self
+    where
+        T: Default,
+    {
+        Self {
+            runs: [(0, 0); MAX_RUNS],
+            len: 0,
+            data: core::array::from_fn(|_| T::default()),
+            data_len: 0,
+        }
+    }
+
+    /// Add a run of the given length at the given index, returning the elements to fill in.
+    fn reserve(&mut self, idx: usize, len: usize) -> Result<&mut [T], CapacityError> {
+        if self.len == MAX_RUNS || MAX_DATA - self.data_len < len {
+            return Err(CapacityError);
+        }
+
+        self.runs[self.len] = (idx, len);
+        self.len += 1;
+        let data = &mut self.data[self.data_len..self.data_len + len];
+        self.data_len += len;
+        Ok(data)
+    }
+}
+
+impl<T, const N: usize, const MAX_RUNS: usize, const MAX_DATA: usize> fmt::Debug
+    for Patch<T, N, MAX_RUNS, MAX_DATA>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.runs()).finish()
+    }
+}
+
+/// An iterator over the runs of a [`Patch`].
+///
+/// Created by [`Patch::runs`].
+#[derive(Clone)]
+pub struct PatchRuns<'p, T> {
+    runs: core::slice::Iter<'p, (usize, usize)>,
+    data: &'p [T],
+}
+
+impl<'p, T> Iterator for PatchRuns<'p, T> {
+    type Item = (usize, &'p [T]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let &(idx, len) = self.runs.next()?;
+        let (diff, rest) = self.data.split_at(len);
+        self.data = rest;
+        Some((idx, diff))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.runs.size_hint()
+    }
+}
+
+impl<T> ExactSizeIterator for PatchRuns<'_, T> {}
+
+impl<T> FusedIterator for PatchRuns<'_, T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_patch_apply() {
+        let a = [0u8; 40];
+        let mut b = [0u8; 40];
+
+        b[..10].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+        b[20..25].copy_from_slice(&[11, 12, 13, 14, 15]);
+        b[39] = 20;
+
+        let patch = Patch::<_, 40, 3, 16>::new(&a, &b).unwrap();
+        assert_eq!(patch.len(), 3);
+
+        let mut runs = a.diff_runs(&b);
+        for run in patch.runs() {
+            assert_eq!(runs.next(), Some(run));
+        }
+        assert_eq!(runs.next(), None);
+
+        let mut c = a;
+        patch.apply(&mut c);
+        assert_eq!(c, b);
+    }
+
+    #[test]
+    fn test_patch_invert() {
+        let mut a = [0u8; 40];
+        let mut b = [0u8; 40];
+
+        a[5..8].copy_from_slice(&[1, 2, 3]);
+        b[6..12].copy_from_slice(&[4, 5, 6, 7, 8, 9]);
+
+        let patch = Patch::<_, 40, 4, 8>::new(&a, &b).unwrap();
+        let undo = patch.invert(&a);
+
+        let mut c = a;
+        patch.apply(&mut c);
+        assert_eq!(c, b);
+        undo.apply(&mut c);
+        assert_eq!(c, a);
+    }
+
+    #[test]
+    fn test_patch_empty() {
+        let a = [0u8; 40];
+        let patch = Patch::<_, 40, 0, 0>::new(&a, &a).unwrap();
+        assert!(patch.is_empty());
+        assert_eq!(patch.runs().next(), None);
+    }
+
+    #[test]
+    fn test_patch_capacity() {
+        let a = [0u8; 10];
+        let b = [1u8, 0, 1, 0, 1, 0, 0, 0, 0, 0];
+
+        assert_eq!(Patch::<_, 10, 2, 5>::new(&a, &b).err(), Some(CapacityError));
+
+        // Merging close runs fits the same difference into fewer runs
+        let options = DiffOptions::new().max_gap(1);
+        let patch = Patch::<_, 10, 2, 5>::new_with(&a, &b, options).unwrap();
+        assert_eq!(patch.len(), 1);
+
+        // But needs more room for the data
+        assert_eq!(
+            Patch::<_, 10, 2, 5>::new_with(&a, &[1; 10], options).err(),
+            Some(CapacityError)
+        );
+    }
+
+    #[test]
+    fn test_patch_size() {
+        // Only the changed elements are kept, not a copy of the whole array
+        let a = [0u8; 4096];
+        let mut b = a;
+        b[1000] = 1;
+
+        let patch = Patch::<_, 4096, 4, 16>::new(&a, &b).unwrap();
+        assert_eq!(patch.data_len(), 1);
+        assert!(core::mem::size_of_val(&patch) < 128);
+    }
+
+    #[test]
+    fn test_patch_compose() {
+        let a = [0u8; 20];
+        let mut b = a;
+        let mut c = a;
+
+        b[2..6].copy_from_slice(&[1, 2, 3, 4]);
+        b[10..12].copy_from_slice(&[5, 6]);
+        c[2..6].copy_from_slice(&[1, 2, 3, 4]);
+        c[4..8].copy_from_slice(&[7, 8, 9, 10]);
+        c[12] = 11;
+        c[18] = 12;
+
+        let first = Patch::<_, 20, 3, 10>::new(&a, &b).unwrap();
+        let second = Patch::<_, 20, 3, 10>::new(&b, &c).unwrap();
+        let both = first.compose(&second).unwrap();
+
+        // Overlapping runs are joined, with the later patch winning
+        let mut runs = both.runs();
+        assert_eq!(runs.next(), Some((2, &[1, 2, 7, 8, 9, 10][..])));
+        assert_eq!(runs.next(), Some((10, &[0, 0, 11][..])));
+        assert_eq!(runs.next(), Some((18, &[12][..])));
+        assert_eq!(runs.next(), None);
+
+        let mut d = a;
+        both.apply(&mut d);
+        assert_eq!(d, c);
+
+        let mut e = a;
+        e[0] = 13;
+        e[15] = 14;
+
+        let first = Patch::<_, 20, 2, 6>::new(&a, &e).unwrap();
+        let second = Patch::<_, 20, 2, 6>::new(&e, &e).unwrap();
+        assert_eq!(first.compose(&second).unwrap().len(), 2);
+        let second = Patch::<_, 20, 2, 6>::new(&a, &b).unwrap();
+        assert_eq!(first.compose(&second).err(), Some(CapacityError));
+    }
+
+    mod compose {
+        extern crate std;
+
+        use super::*;
+        use proptest::prelude::*;
+
+        const LEN: usize = 32;
+
+        fn arrays() -> impl Strategy<Value = [u8; LEN]> {
+            // Few distinct values, so arrays share runs of equal elements
+            proptest::array::uniform32(0u8..3)
+        }
+
+        proptest! {
+            #[test]
+            fn compose_equals_sequential_apply(a in arrays(), b in arrays(), c in arrays()) {
+                let first = Patch::<_, LEN, LEN, LEN>::new(&a, &b).unwrap();
+                let second = Patch::<_, LEN, LEN, LEN>::new(&b, &c).unwrap();
+                let both = first.compose(&second).unwrap();
+
+                let mut sequential = a;
+                first.apply(&mut sequential);
+                second.apply(&mut sequential);
+
+                let mut composed = a;
+                both.apply(&mut composed);
+                prop_assert_eq!(composed, sequential);
+                prop_assert_eq!(composed, c);
+            }
+
+            #[test]
+            fn compose_runs_are_disjoint(a in arrays(), b in arrays(), c in arrays()) {
+                let first = Patch::<_, LEN, LEN, LEN>::new(&a, &b).unwrap();
+                let second = Patch::<_, LEN, LEN, LEN>::new(&b, &c).unwrap();
+                let both = first.compose(&second).unwrap();
+
+                prop_assert!(both.len() <= first.len() + second.len());
+                let mut previous_end = None;
+                for (idx, diff) in both.runs() {
+                    prop_assert!(!diff.is_empty());
+                    if let Some(end) = previous_end {
+                        prop_assert!(idx > end);
+                    }
+                    previous_end = Some(idx + diff.len());
+                }
+            }
+        }
+    }
+}
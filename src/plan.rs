@@ -0,0 +1,263 @@
+use core::convert::Infallible;
+use core::ops::Range;
+
+use crate::DiffInPlace;
+
+/// The cost of writing runs of elements over a bus, used to plan the cheapest set of writes.
+///
+/// Every write transaction pays a fixed overhead (e.g. a start condition, the device address
+/// and the register pointer), plus a cost for every element transferred.
+pub trait CostModel {
+    /// The fixed cost of a single write transaction, regardless of its length.
+    fn transaction_overhead(&self) -> usize;
+
+    /// The cost of transferring a single element.
+    fn element_cost(&self) -> usize;
+
+    /// The fixed cost of a single transaction writing the whole array.
+    ///
+    /// Defaults to [`CostModel::transaction_overhead`], override it for chips which have
+    /// cheaper framing for writing everything at once (e.g. no register pointer).
+    fn full_write_overhead(&self) -> usize {
+        self.transaction_overhead()
+    }
+}
+
+/// A [`CostModel`] with constant costs.
+///
+/// # Example
+/// ```
+///     use diff_in_place::{DiffInPlace, LinearCost};
+///     let a = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+///     let b = [1, 0, 2, 0, 0, 0, 0, 0, 0, 3];
+///
+///     // Each write costs 3 bytes of framing plus one byte per element
+///     let model = LinearCost::new(3, 1);
+///     a.diff_in_place_planned(&b, &model, |idx, diff| {
+///         // println!("{}: {:?}", idx, diff);
+///         // Prints:
+///         // 0: [1, 0, 2]
+///         // 9: [3]
+///     });
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LinearCost {
+    transaction_overhead: usize,
+    element_cost: usize,
+}
+
+impl LinearCost {
+    /// Create a cost model with the given per-transaction overhead and per-element cost.
+    pub const fn new(transaction_overhead: usize, element_cost: usize) -> Self {
+        Self {
+            transaction_overhead,
+            element_cost,
+        }
+    }
+}
+
+impl CostModel for LinearCost {
+    fn transaction_overhead(&self) -> usize {
+        self.transaction_overhead
+    }
+
+    fn element_cost(&self) -> usize {
+        self.element_cost
+    }
+}
+
+/// Accumulates runs, merging each run into the previous one while that is cheaper.
+///
+/// Since the cost of a write is linear in its length, merging two neighbouring writes
+/// only depends on the gap between them, so deciding greedily yields the cheapest plan.
+struct Planner<'m, M> {
+    model: &'m M,
+    pending: Option<Range<usize>>,
+}
+
+impl<'m, M> Planner<'m, M>
+where
+    M: CostModel,
+{
+    fn new(model: &'m M) -> Self {
+        Self {
+            model,
+            pending: None,
+        }
+    }
+
+    /// Add the next run, returning the previous write if it can no longer grow.
+    fn push(&mut self, run: Range<usize>) -> Option<Range<usize>> {
+        match self.pending.clone() {
+            Some(pending) if self.merge_is_cheaper(pending.end, run.start) => {
+                self.pending = Some(pending.start..run.end);
+                None
+            }
+            _ => self.pending.replace(run),
+        }
+    }
+
+    /// Take the last pending write.
+    fn finish(&mut self) -> Option<Range<usize>> {
+        self.pending.take()
+    }
+
+    fn merge_is_cheaper(&self, end: usize, start: usize) -> bool {
+        // On a tie prefer fewer transactions
+        let gap_cost = (start - end).saturating_mul(self.model.element_cost());
+        gap_cost <= self.model.transaction_overhead()
+    }
+
+    fn write_cost(&self, run: &Range<usize>) -> usize {
+        run.len()
+            .saturating_mul(self.model.element_cost())
+            .saturating_add(self.model.transaction_overhead())
+    }
+}
+
+/// Emit the cheapest set of writes covering all differences between `current` and `other`.
+pub(crate) fn try_diff_in_place_planned<D, T, M, F, R, const N: usize>(
+    current: &D,
+    other: &[T; N],
+    model: &M,
+    mut func: F,
+) -> Result<(), R>
+where
+    D: DiffInPlace<T, N> + ?Sized,
+    T: PartialEq,
+    M: CostModel,
+    F: FnMut(usize, &[T]) -> Result<(), R>,
+{
+    // First pass, find out how much the planned writes would cost
+    let mut planner = Planner::new(model);
+    let mut planned_cost: usize = 0;
+    current
+        .try_diff_in_place(other, |idx, diff| -> Result<(), Infallible> {
+            if let Some(write) = planner.push(idx..idx + diff.len()) {
+                planned_cost = planned_cost.saturating_add(planner.write_cost(&write));
+            }
+            Ok(())
+        })
+        .unwrap_or_else(|never| match never {});
+    let Some(write) = planner.finish() else {
+        // Nothing to write
+        return Ok(());
+    };
+    planned_cost = planned_cost.saturating_add(planner.write_cost(&write));
+
+    // Fall back to writing everything at once, if that is cheaper
+    let full_write_cost = N
+        .saturating_mul(model.element_cost())
+        .saturating_add(model.full_write_overhead());
+    if full_write_cost < planned_cost {
+        return func(0, other);
+    }
+
+    // Second pass, emit the planned writes
+    let mut planner = Planner::new(model);
+    current.try_diff_in_place(other, |idx, diff| {
+        match planner.push(idx..idx + diff.len()) {
+            Some(write) => func(write.start, &other[write]),
+            None => Ok(()),
+        }
+    })?;
+    match planner.finish() {
+        Some(write) => func(write.start, &other[write]),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct CheapFullWrite;
+
+    impl CostModel for CheapFullWrite {
+        fn transaction_overhead(&self) -> usize {
+            4
+        }
+
+        fn element_cost(&self) -> usize {
+            1
+        }
+
+        fn full_write_overhead(&self) -> usize {
+            0
+        }
+    }
+
+    #[test]
+    fn test_planned_merges_cheap_gaps() {
+        let a = [0u8; 40];
+        let mut b = [0u8; 40];
+
+        b[2] = 1;
+        b[5] = 2;
+        b[20] = 3;
+
+        const EXPECTED_CALLS: [(usize, &[u8]); 2] = [(2usize, &[1, 0, 0, 2]), (20usize, &[3])];
+
+        let mut call_idx = 0;
+        a.diff_in_place_planned(&b, &LinearCost::new(2, 1), |idx, diff| {
+            let (expected_idx, expected_diff) = EXPECTED_CALLS[call_idx];
+            assert_eq!(idx, expected_idx);
+            assert_eq!(diff, expected_diff);
+            call_idx += 1;
+        });
+        assert_eq!(call_idx, EXPECTED_CALLS.len());
+    }
+
+    #[test]
+    fn test_planned_without_overhead_is_plain_diff() {
+        let a = [0u8, 1, 2, 3, 4, 5, 6, 7, 8, 9];
+        let b = [1u8, 1, 0, 0, 4, 5, 0, 7, 8, 0];
+
+        let mut runs = a.diff_runs(&b);
+        a.diff_in_place_planned(&b, &LinearCost::new(0, 1), |idx, diff| {
+            assert_eq!(runs.next(), Some((idx, diff)));
+        });
+        assert_eq!(runs.next(), None);
+    }
+
+    #[test]
+    fn test_planned_full_write_fallback() {
+        let a = [0u8; 10];
+        let mut b = [0u8; 10];
+
+        b[1] = 1;
+        b[5] = 2;
+        b[8] = 3;
+
+        let mut called = false;
+        a.diff_in_place_planned(&b, &CheapFullWrite, |idx, diff| {
+            assert_eq!(idx, 0);
+            assert_eq!(diff, &b);
+            called = true;
+        });
+        assert!(called);
+    }
+
+    #[test]
+    fn test_planned_fully_same() {
+        let a = [0u8; 40];
+        let b = [0u8; 40];
+        a.diff_in_place_planned(&b, &CheapFullWrite, |_, _| panic!("Should not be called"))
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_fallible_planned() {
+        let a = [0u8; 40];
+        let mut b = [0u8; 40];
+
+        b[..10].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+
+        a.try_diff_in_place_planned(
+            &b,
+            &LinearCost::new(2, 1),
+            |_idx, _diff| -> Result<(), ()> { Err(()) },
+        )
+        .unwrap();
+    }
+}
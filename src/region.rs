@@ -0,0 +1,204 @@
+use core::ops::Range;
+
+/// How an address in a register file may be accessed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Access {
+    /// Readable and writable, writing back the current value is harmless.
+    ReadWrite,
+    /// Writable but not readable, only written when its value changes since writing may
+    /// trigger an action.
+    WriteOnly,
+    /// Writing a one to a bit clears it, only written when its value changes since writing
+    /// back the current value would clear any set bits.
+    WriteOneToClear,
+    /// Never written, differences are dropped.
+    ReadOnly,
+    /// Never written, differences are dropped.
+    Reserved,
+}
+
+impl Access {
+    /// Returns whether addresses with this access may be written when their value changes.
+    pub const fn is_writable(self) -> bool {
+        matches!(
+            self,
+            Access::ReadWrite | Access::WriteOnly | Access::WriteOneToClear
+        )
+    }
+
+    /// Returns whether addresses with this access may be written with their current value,
+    /// which is required for runs to be merged or widened across them.
+    pub const fn is_rewritable(self) -> bool {
+        matches!(self, Access::ReadWrite)
+    }
+}
+
+/// A range of addresses sharing the same access.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub range: Range<usize>,
+    pub access: Access,
+}
+
+impl Region {
+    /// Create a region with the given access over the given range of addresses.
+    pub const fn new(range: Range<usize>, access: Access) -> Self {
+        Self { range, access }
+    }
+}
+
+/// A description of the access of every address in a register file.
+///
+/// When set with [`DiffOptions::regions`](crate::DiffOptions::regions), runs never cover
+/// addresses which are not writable, and runs are only merged or widened across addresses
+/// which are safe to rewrite.
+///
+/// # Example
+/// ```
+///     use diff_in_place::{Access, DiffInPlace, DiffOptions, Region, RegionMap};
+///     const REGIONS: [Region; 2] = [
+///         Region::new(2..3, Access::ReadOnly),
+///         Region::new(5..6, Access::WriteOneToClear),
+///     ];
+///
+///     let a = [0, 0, 0, 0, 0, 0, 0, 0];
+///     let b = [0, 1, 2, 3, 0, 0, 4, 0];
+///
+///     let options = DiffOptions::new()
+///         .max_gap(2)
+///         .regions(RegionMap::new(&REGIONS));
+///     a.diff_in_place_with(&b, options, |idx, diff| {
+///         // println!("{}: {:?}", idx, diff);
+///         // Prints:
+///         // 1: [1]
+///         // 3: [3]
+///         // 6: [4]
+///     });
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RegionMap<'r> {
+    regions: &'r [Region],
+    default: Access,
+}
+
+impl<'r> RegionMap<'r> {
+    /// Create a map from the given regions, any address not covered by them is
+    /// [`Access::ReadWrite`].
+    ///
+    /// If regions overlap, the first one covering an address applies.
+    pub const fn new(regions: &'r [Region]) -> Self {
+        Self {
+            regions,
+            default: Access::ReadWrite,
+        }
+    }
+
+    /// Set the access of addresses not covered by any of the regions.
+    pub const fn default_access(mut self, access: Access) -> Self {
+        self.default = access;
+        self
+    }
+
+    /// Returns the access of the given address.
+    pub fn access(&self, idx: usize) -> Access {
+        self.regions
+            .iter()
+            .find(|region| region.range.contains(&idx))
+            .map_or(self.default, |region| region.access)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DiffInPlace, DiffOptions};
+
+    const REGIONS: [Region; 3] = [
+        Region::new(0..2, Access::ReadOnly),
+        Region::new(8..10, Access::WriteOnly),
+        Region::new(16..20, Access::Reserved),
+    ];
+
+    #[test]
+    fn test_region_access() {
+        let map = RegionMap::new(&REGIONS);
+        assert_eq!(map.access(1), Access::ReadOnly);
+        assert_eq!(map.access(2), Access::ReadWrite);
+        assert_eq!(map.access(9), Access::WriteOnly);
+        assert_eq!(map.access(19), Access::Reserved);
+
+        let map = map.default_access(Access::Reserved);
+        assert_eq!(map.access(2), Access::Reserved);
+    }
+
+    #[test]
+    fn test_regions_drop_read_only() {
+        let a = [0u8; 24];
+        let b = [1u8; 24];
+
+        const EXPECTED_CALLS: [(usize, &[u8]); 2] = [(2usize, &[1; 14]), (20usize, &[1; 4])];
+
+        let options = DiffOptions::new().regions(RegionMap::new(&REGIONS));
+        let mut call_idx = 0;
+        a.diff_in_place_with(&b, options, |idx, diff| {
+            let (expected_idx, expected_diff) = EXPECTED_CALLS[call_idx];
+            assert_eq!(idx, expected_idx);
+            assert_eq!(diff, expected_diff);
+            call_idx += 1;
+        });
+        assert_eq!(call_idx, EXPECTED_CALLS.len());
+    }
+
+    #[test]
+    fn test_regions_merge_only_across_rewritable() {
+        let a = [0u8; 24];
+        let mut b = [0u8; 24];
+
+        b[5] = 1;
+        b[7] = 2;
+        b[10] = 3;
+        b[15] = 4;
+        b[21] = 5;
+
+        const EXPECTED_CALLS: [(usize, &[u8]); 3] = [
+            (5usize, &[1, 0, 2]),
+            (10usize, &[3, 0, 0, 0, 0, 4]),
+            (21usize, &[5]),
+        ];
+
+        let options = DiffOptions::new()
+            .max_gap(4)
+            .regions(RegionMap::new(&REGIONS));
+        let mut call_idx = 0;
+        a.diff_in_place_with(&b, options, |idx, diff| {
+            let (expected_idx, expected_diff) = EXPECTED_CALLS[call_idx];
+            assert_eq!(idx, expected_idx);
+            assert_eq!(diff, expected_diff);
+            call_idx += 1;
+        });
+        assert_eq!(call_idx, EXPECTED_CALLS.len());
+    }
+
+    #[test]
+    fn test_regions_limit_alignment() {
+        let a = [0u8; 12];
+        let mut b = [0u8; 12];
+
+        b[3] = 1;
+        b[9] = 2;
+
+        const EXPECTED_CALLS: [(usize, &[u8]); 2] = [(3usize, &[1]), (9usize, &[2, 0, 0])];
+
+        let options = DiffOptions::new()
+            .align(4)
+            .regions(RegionMap::new(&REGIONS));
+        let mut call_idx = 0;
+        a.diff_in_place_with(&b, options, |idx, diff| {
+            let (expected_idx, expected_diff) = EXPECTED_CALLS[call_idx];
+            assert_eq!(idx, expected_idx);
+            assert_eq!(diff, expected_diff);
+            call_idx += 1;
+        });
+        assert_eq!(call_idx, EXPECTED_CALLS.len());
+    }
+}
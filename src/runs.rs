@@ -0,0 +1,332 @@
+use core::iter::FusedIterator;
+use core::ops::Range;
+
+use crate::compare::{Compare, Equal};
+use crate::{Access, DiffOptions};
+
+#[derive(Copy, Clone)]
+enum DiffState {
+    Same,
+    Different(usize),
+}
+
+/// A lazy iterator over the runs of different elements between two equally sized slices.
+///
+/// Each item is the index into the slices where the run starts, along with the slice
+/// of different elements from the other slice.
+///
+/// Whether two elements are the same is decided by the comparator `C`, which defaults
+/// to their [`PartialEq`] implementation.
+///
+/// Created by [`DiffInPlace::diff_runs`](crate::DiffInPlace::diff_runs),
+/// [`DiffInPlace::diff_runs_with`](crate::DiffInPlace::diff_runs_with) and
+/// [`DiffInPlace::diff_runs_by`](crate::DiffInPlace::diff_runs_by).
+#[derive(Clone)]
+pub struct DiffRuns<'a, T, C = Equal> {
+    left: &'a [T],
+    right: &'a [T],
+    compare: C,
+    cursor: Cursor<'a>,
+}
+
+/// The progress of finding runs, detached from the slices being compared.
+///
+/// This allows the compared slices to be modified between runs, as long as only the
+/// elements of runs which were already found are modified.
+#[derive(Clone)]
+pub(crate) struct Cursor<'r> {
+    options: DiffOptions<'r>,
+    position: usize,
+    pending: Option<Range<usize>>,
+}
+
+impl<'r> Cursor<'r> {
+    pub(crate) fn new(options: DiffOptions<'r>) -> Self {
+        // Otherwise splitting at page boundaries would split aligned units
+        assert!(
+            options.page_size == usize::MAX || options.page_size % options.align == 0,
+            "page_size must be a multiple of align"
+        );
+        Self {
+            options,
+            position: 0,
+            pending: None,
+        }
+    }
+
+    /// Find the next run of different elements between the two slices.
+    pub(crate) fn next_run<T>(&mut self, left: &[T], right: &[T]) -> Option<Range<usize>>
+    where
+        T: PartialEq,
+    {
+        let mut runs = DiffRuns {
+            left,
+            right,
+            compare: Equal,
+            cursor: self.clone(),
+        };
+        let run = runs.next_chunk();
+        self.position = runs.cursor.position;
+        self.pending = runs.cursor.pending;
+        run
+    }
+}
+
+impl<'a, T> DiffRuns<'a, T>
+where
+    T: PartialEq,
+{
+    pub(crate) fn new(left: &'a [T], right: &'a [T]) -> Self {
+        debug_assert_eq!(left.len(), right.len());
+        Self {
+            left,
+            right,
+            compare: Equal,
+            cursor: Cursor::new(DiffOptions::new()),
+        }
+    }
+}
+
+impl<'a, T, C> DiffRuns<'a, T, C>
+where
+    C: Compare<T>,
+{
+    /// Shape the runs according to the given options.
+    ///
+    /// This should be called before iterating, as it restarts the diff from the beginning.
+    ///
+    /// # Example
+    /// ```
+    ///     use diff_in_place::{DiffInPlace, DiffOptions};
+    ///     let a = [0.0f32, 0.0, 0.0, 0.0, 0.0, 0.0];
+    ///     let b = [0.1, 0.0, 5.0, 0.0, 0.0, 0.0];
+    ///
+    ///     let options = DiffOptions::new().max_gap(1);
+    ///     let mut runs = a.diff_runs_by(&b, |x, y| (x - y).abs() < 0.5).with_options(options);
+    ///     assert_eq!(runs.next(), Some((2, &[5.0][..])));
+    ///     assert_eq!(runs.next(), None);
+    /// ```
+    pub fn with_options(mut self, options: DiffOptions<'a>) -> Self {
+        self.cursor = Cursor::new(options);
+        self
+    }
+
+    pub(crate) fn with_compare<D>(self, compare: D) -> DiffRuns<'a, T, D>
+    where
+        D: Compare<T>,
+    {
+        DiffRuns {
+            left: self.left,
+            right: self.right,
+            compare,
+            cursor: self.cursor,
+        }
+    }
+
+    /// Find the next maximal run of different elements, starting at the current position.
+    fn next_raw(&mut self) -> Option<Range<usize>> {
+        // Skip the leading elements the comparator knows are the same, then go over the
+        // remaining elements in both slices, comparing them.
+        // Stop at the end of the first different run, and remember where we stopped
+        // so the next call picks up from there.
+        let position = self.cursor.position;
+        let skipped = self
+            .compare
+            .skip_same(&self.left[position..], &self.right[position..]);
+        let start = position + skipped;
+        let mut run_state = DiffState::Same;
+        for current in start..self.left.len() {
+            match (run_state, self.is_same(current)) {
+                (DiffState::Same, false) => {
+                    // We are starting an unequal run, preserve the current index
+                    run_state = DiffState::Different(current);
+                }
+                (DiffState::Different(run_start), true) => {
+                    // We are ending an unequal run, return it
+                    self.cursor.position = current;
+                    return Some(run_start..current);
+                }
+                _ => {
+                    // Run state is unchanged
+                }
+            }
+        }
+
+        // We have reached the end, if we are still in a different run, return it
+        self.cursor.position = self.left.len();
+        match run_state {
+            DiffState::Different(run_start) => Some(run_start..self.left.len()),
+            DiffState::Same => None,
+        }
+    }
+
+    /// Find the next run of different elements, widened to the alignment.
+    fn next_aligned(&mut self) -> Option<Range<usize>> {
+        let run = self.next_raw()?;
+        let align = self.cursor.options.align;
+        let mut start = run.start - run.start % align;
+        let mut end = run
+            .end
+            .div_ceil(align)
+            .saturating_mul(align)
+            .min(self.left.len());
+
+        // Widening rewrites the elements we widened into, which is only allowed if harmless.
+        // Whole units are only found different if they are safe to write whole.
+        if !self.cursor.options.whole_units {
+            if !self.is_rewritable(start..run.start) {
+                start = run.start;
+            }
+            if !self.is_rewritable(run.end..end) {
+                end = run.end;
+            }
+        }
+
+        // The elements we widened into are already covered by this run
+        self.cursor.position = self.cursor.position.max(end);
+        Some(start..end)
+    }
+
+    /// Find the next run, merging in following runs that are close enough.
+    fn next_range(&mut self) -> Option<Range<usize>> {
+        let mut run = self.next_aligned()?;
+        let align = self.cursor.options.align;
+
+        // Only look ahead as far as the allowed gap, so that runs are still found lazily.
+        // The next run is within reach if its aligned start is, so the window is widened
+        // up to the next alignment boundary.
+        while run.end < self.left.len() {
+            let reach = run.end.saturating_add(self.cursor.options.max_gap);
+            let window_end = (reach / align)
+                .saturating_add(1)
+                .saturating_mul(align)
+                .min(self.left.len());
+            match (run.end..window_end).position(|idx| !self.is_same(idx)) {
+                Some(offset) => {
+                    // The next run is within reach, merge it into this one if it is
+                    // harmless to rewrite the elements in between
+                    let next_start = run.end + offset;
+                    if !self.is_rewritable(run.end..next_start) {
+                        break;
+                    }
+                    self.cursor.position = next_start;
+                    if let Some(next) = self.next_aligned() {
+                        run.end = next.end;
+                    }
+                }
+                None => break,
+            }
+        }
+
+        Some(run)
+    }
+
+    /// Find the next run, split to the maximal run length and page boundaries.
+    fn next_chunk(&mut self) -> Option<Range<usize>> {
+        let run = match self.cursor.pending.take() {
+            Some(rest) => rest,
+            None => self.next_range()?,
+        };
+
+        let align = self.cursor.options.align;
+        let page_size = self.cursor.options.page_size;
+        let max_run_len = (self.cursor.options.max_run_len / align).max(1) * align;
+        let page_end = (run.start / page_size)
+            .saturating_add(1)
+            .saturating_mul(page_size);
+        let mut end = run
+            .start
+            .saturating_add(max_run_len)
+            .min(page_end)
+            .min(run.end);
+        if end < run.end {
+            // Neither this chunk nor the rest of the run should be padded with equal
+            // elements, these were only there to merge runs together
+            while end > run.start.saturating_add(align) && self.is_unit_same(end - align..end) {
+                end -= align;
+            }
+            let mut rest = end..run.end;
+            while !rest.is_empty()
+                && self.is_unit_same(rest.start..rest.start.saturating_add(align).min(rest.end))
+            {
+                rest.start += align;
+            }
+            if !rest.is_empty() {
+                self.cursor.pending = Some(rest);
+            }
+        }
+
+        Some(run.start..end)
+    }
+
+    fn is_unit_same(&mut self, unit: Range<usize>) -> bool {
+        unit.into_iter().all(|idx| self.is_same(idx))
+    }
+
+    fn is_same(&mut self, idx: usize) -> bool {
+        if self.cursor.options.whole_units && self.cursor.options.regions.is_some() {
+            return !self.is_unit_writable(idx) || self.is_element_same(idx);
+        }
+        !self.access(idx).is_writable() || self.is_element_same(idx)
+    }
+
+    fn is_element_same(&mut self, idx: usize) -> bool {
+        self.compare.same(idx, &self.left[idx], &self.right[idx])
+    }
+
+    /// Returns whether the unit holding the given index can be written whole, which needs
+    /// every element in it to be writable, and either changed or safe to rewrite.
+    fn is_unit_writable(&mut self, idx: usize) -> bool {
+        let align = self.cursor.options.align;
+        let start = idx - idx % align;
+        let end = start.saturating_add(align).min(self.left.len());
+        (start..end).all(|idx| {
+            let access = self.access(idx);
+            access.is_writable() && (access.is_rewritable() || !self.is_element_same(idx))
+        })
+    }
+
+    fn is_rewritable(&self, range: Range<usize>) -> bool {
+        match self.cursor.options.regions {
+            Some(map) => range.into_iter().all(|idx| map.access(idx).is_rewritable()),
+            None => true,
+        }
+    }
+
+    fn access(&self, idx: usize) -> Access {
+        self.cursor
+            .options
+            .regions
+            .map_or(Access::ReadWrite, |map| map.access(idx))
+    }
+}
+
+impl<'a, T, C> Iterator for DiffRuns<'a, T, C>
+where
+    C: Compare<T>,
+{
+    type Item = (usize, &'a [T]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let run = self.next_chunk()?;
+        Some((run.start, &self.right[run]))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let pending = self.cursor.pending.as_ref().map_or(0, Range::len);
+
+        // Runs are separated by at least one equal element, unless they are split
+        let remaining = self.left.len() - self.cursor.position;
+        let splits = self.cursor.options.max_run_len < remaining
+            || self.cursor.options.page_size < usize::MAX;
+        let remaining = if splits {
+            remaining
+        } else {
+            remaining.div_ceil(2)
+        };
+
+        (usize::from(pending > 0), Some(pending + remaining))
+    }
+}
+
+impl<T, C> FusedIterator for DiffRuns<'_, T, C> where C: Compare<T> {}
@@ -0,0 +1,215 @@
+use core::convert::Infallible;
+
+use crate::{DiffOptions, DiffRuns};
+
+/// A single difference between two slices of possibly different lengths.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SliceDiff<'a, T> {
+    /// A run of different elements present in both slices, with the index into the slices
+    /// and the slice of different elements from the other slice.
+    Changed(usize, &'a [T]),
+    /// Trailing elements present only in the other slice, with the index at which they
+    /// start and the elements from the other slice.
+    Appended(usize, &'a [T]),
+    /// Trailing elements present only in this slice, with the index at which they start
+    /// and the elements from this slice.
+    Truncated(usize, &'a [T]),
+}
+
+pub trait DiffSlice<T>
+where
+    T: PartialEq,
+{
+    /// Fallible version of `diff_slice_with` for propagating errors.
+    /// Perform an in-place diff between two slices, invoking the given function for each
+    /// run of different elements as shaped by the given options, and then for any trailing
+    /// elements present in only one of the slices.
+    ///
+    /// # Arguments
+    /// * `other`   - The other slice to compare against.
+    /// * `options` - The options controlling how runs are reported.
+    /// * `func`    - The function to call for each difference.
+    fn try_diff_slice_with<F, R>(
+        &self,
+        other: &[T],
+        options: DiffOptions,
+        func: F,
+    ) -> Result<(), R>
+    where
+        F: FnMut(SliceDiff<'_, T>) -> Result<(), R>;
+
+    /// Fallible version of `diff_slice` for propagating errors.
+    /// Perform an in-place diff between two slices, invoking the given function for each
+    /// run of different elements, and then for any trailing elements present in only
+    /// one of the slices.
+    ///
+    /// # Arguments
+    /// * `other`   - The other slice to compare against.
+    /// * `func`    - The function to call for each difference.
+    fn try_diff_slice<F, R>(&self, other: &[T], func: F) -> Result<(), R>
+    where
+        F: FnMut(SliceDiff<'_, T>) -> Result<(), R>,
+    {
+        self.try_diff_slice_with(other, DiffOptions::new(), func)
+    }
+
+    /// Perform an in-place diff between two slices, invoking the given function for each
+    /// run of different elements as shaped by the given options, and then for any trailing
+    /// elements present in only one of the slices.
+    ///
+    /// The options only apply to runs of different elements, trailing elements are always
+    /// reported as a whole.
+    ///
+    /// # Arguments
+    /// * `other`   - The other slice to compare against.
+    /// * `options` - The options controlling how runs are reported.
+    /// * `func`    - The function to call for each difference.
+    fn diff_slice_with<F>(&self, other: &[T], options: DiffOptions, mut func: F)
+    where
+        F: FnMut(SliceDiff<'_, T>),
+    {
+        self.try_diff_slice_with(other, options, |diff| -> Result<(), Infallible> {
+            func(diff);
+            Ok(())
+        })
+        .unwrap_or_else(|never| match never {});
+    }
+
+    /// Perform an in-place diff between two slices, invoking the given function for each
+    /// run of different elements, and then for any trailing elements present in only
+    /// one of the slices.
+    ///
+    /// # Arguments
+    /// * `other`   - The other slice to compare against.
+    /// * `func`    - The function to call for each difference.
+    ///
+    /// # Example
+    /// ```
+    ///     use diff_in_place::{DiffSlice, SliceDiff};
+    ///     let a: &[u8] = &[0, 0, 0, 0, 0, 0];
+    ///     let b: &[u8] = &[0, 1, 0, 0, 0, 0, 2, 3];
+    ///
+    ///     a.diff_slice(b, |diff| {
+    ///         // println!("{:?}", diff);
+    ///         // Prints:
+    ///         // Changed(1, [1])
+    ///         // Appended(6, [2, 3])
+    ///     });
+    /// ```
+    fn diff_slice<F>(&self, other: &[T], func: F)
+    where
+        F: FnMut(SliceDiff<'_, T>),
+    {
+        self.diff_slice_with(other, DiffOptions::new(), func)
+    }
+}
+
+impl<T> DiffSlice<T> for [T]
+where
+    T: PartialEq,
+{
+    fn try_diff_slice_with<F, R>(
+        &self,
+        other: &[T],
+        options: DiffOptions,
+        mut func: F,
+    ) -> Result<(), R>
+    where
+        F: FnMut(SliceDiff<'_, T>) -> Result<(), R>,
+    {
+        // Diff the elements present in both slices
+        let common = self.len().min(other.len());
+        let runs = DiffRuns::new(&self[..common], &other[..common]).with_options(options);
+        for (idx, diff) in runs {
+            func(SliceDiff::Changed(idx, diff))?;
+        }
+
+        // At most one of the slices has trailing elements
+        if other.len() > common {
+            func(SliceDiff::Appended(common, &other[common..]))?;
+        }
+        if self.len() > common {
+            func(SliceDiff::Truncated(common, &self[common..]))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DiffInPlace;
+
+    #[test]
+    fn test_slice_same_length() {
+        let a = [0u8, 1, 2, 3, 4, 5, 6, 7, 8, 9];
+        let b = [1u8, 1, 0, 0, 4, 5, 0, 7, 8, 0];
+
+        let mut runs = a.diff_runs(&b);
+        a.diff_slice(&b, |diff| {
+            let (expected_idx, expected_diff) = runs.next().unwrap();
+            assert_eq!(diff, SliceDiff::Changed(expected_idx, expected_diff));
+        });
+        assert_eq!(runs.next(), None);
+    }
+
+    #[test]
+    fn test_slice_appended() {
+        let a: &[u8] = &[0, 0, 0, 0];
+        let b: &[u8] = &[0, 1, 0, 0, 2, 3];
+
+        const EXPECTED_CALLS: [SliceDiff<'static, u8>; 2] =
+            [SliceDiff::Changed(1, &[1]), SliceDiff::Appended(4, &[2, 3])];
+
+        let mut call_idx = 0;
+        a.diff_slice(b, |diff| {
+            assert_eq!(diff, EXPECTED_CALLS[call_idx]);
+            call_idx += 1;
+        });
+        assert_eq!(call_idx, EXPECTED_CALLS.len());
+    }
+
+    #[test]
+    fn test_slice_truncated() {
+        let a: &[u8] = &[0, 0, 0, 0, 4, 5];
+        let b: &[u8] = &[0, 0, 0, 1];
+
+        const EXPECTED_CALLS: [SliceDiff<'static, u8>; 2] = [
+            SliceDiff::Changed(3, &[1]),
+            SliceDiff::Truncated(4, &[4, 5]),
+        ];
+
+        let mut call_idx = 0;
+        a.diff_slice(b, |diff| {
+            assert_eq!(diff, EXPECTED_CALLS[call_idx]);
+            call_idx += 1;
+        });
+        assert_eq!(call_idx, EXPECTED_CALLS.len());
+    }
+
+    #[test]
+    fn test_slice_empty() {
+        let a: &[u8] = &[];
+        let b: &[u8] = &[1, 2];
+
+        let mut called = false;
+        a.diff_slice(b, |diff| {
+            assert_eq!(diff, SliceDiff::Appended(0, &[1, 2]));
+            called = true;
+        });
+        assert!(called);
+
+        b.diff_slice(b, |_| panic!("Should not be called"));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_fallible_diff_slice() {
+        let a: &[u8] = &[0, 0];
+        let b: &[u8] = &[0, 0, 1];
+
+        a.try_diff_slice(b, |_diff| -> Result<(), ()> { Err(()) })
+            .unwrap();
+    }
+}
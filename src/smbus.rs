@@ -0,0 +1,303 @@
+//! SMBus block writes of the runs of different registers, with an optional Packet Error Code.
+//!
+//! Each run is sent as a block write to the register it starts at. The frame passed to the
+//! transport holds everything after the address byte:
+//!
+//! ```text
+//! command  count  data[count]  [pec]
+//! ```
+//!
+//! `command` is the register the run starts at, `count` is the number of data bytes, which
+//! is at most [`BLOCK_MAX`], and `pec` is the CRC-8 of the address byte, command, count and
+//! data, sent only when enabled.
+
+use crate::{DiffInPlace, DiffOptions};
+
+/// The maximal number of data bytes of a single block write.
+pub const BLOCK_MAX: usize = 32;
+
+/// The maximal length of a frame, with its command, count, data and PEC.
+pub const FRAME_MAX: usize = BLOCK_MAX + 3;
+
+/// The CRC-8 used for the Packet Error Code, with the polynomial x^8 + x^2 + x + 1.
+const CRC_TABLE: [u8; 256] = {
+    let mut table = [0; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        let mut crc = byte as u8;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        table[byte] = crc;
+        byte += 1;
+    }
+    table
+};
+
+/// Continue computing a Packet Error Code over the given bytes.
+///
+/// The Packet Error Code of a whole message is computed by starting from zero.
+///
+/// # Arguments
+/// * `crc`     - The Packet Error Code of the bytes before.
+/// * `bytes`   - The bytes to compute it over.
+///
+/// # Example
+/// ```
+///     use diff_in_place::smbus::pec;
+///     assert_eq!(pec(0, b"123456789"), 0xf4);
+///     assert_eq!(pec(pec(0, b"1234"), b"56789"), 0xf4);
+/// ```
+pub fn pec(crc: u8, bytes: &[u8]) -> u8 {
+    bytes
+        .iter()
+        .fold(crc, |crc, byte| CRC_TABLE[usize::from(crc ^ byte)])
+}
+
+/// Sends the runs of different registers of a device as SMBus block writes.
+///
+/// # Example
+/// ```
+///     use diff_in_place::SmbusBlockWrite;
+///     let mut shadow = [0u8; 64];
+///     let mut desired = [0u8; 64];
+///     desired[0x10..0x14].copy_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+///
+///     let writer = SmbusBlockWrite::new(0x5a).pec(true);
+///     writer
+///         .try_sync_from(&mut shadow, &desired, |address, frame| {
+///             // i2c.write(address, frame)
+///             assert_eq!(address, 0x5a);
+///             assert_eq!(frame, &[0x10, 4, 0xde, 0xad, 0xbe, 0xef, 0x56]);
+///             Ok::<_, ()>(())
+///         })
+///         .unwrap();
+///     assert_eq!(shadow, desired);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SmbusBlockWrite {
+    address: u8,
+    pec: bool,
+}
+
+impl SmbusBlockWrite {
+    /// Create block writes to the device at the given 7-bit address, without a Packet
+    /// Error Code.
+    pub const fn new(address: u8) -> Self {
+        Self {
+            address,
+            pec: false,
+        }
+    }
+
+    /// Set whether a Packet Error Code is appended to every frame.
+    pub const fn pec(mut self, pec: bool) -> Self {
+        self.pec = pec;
+        self
+    }
+
+    /// Build the frame of a block write of the given data to the given register.
+    ///
+    /// # Arguments
+    /// * `command` - The register the data is written to.
+    /// * `data`    - The data to write.
+    /// * `buffer`  - The buffer to build the frame in.
+    ///
+    /// # Panics
+    /// Panics if there are more than [`BLOCK_MAX`] bytes of data.
+    pub fn frame<'b>(&self, command: u8, data: &[u8], buffer: &'b mut [u8; FRAME_MAX]) -> &'b [u8] {
+        assert!(data.len() <= BLOCK_MAX, "too much data for a block write");
+
+        let mut len = data.len() + 2;
+        buffer[0] = command;
+        buffer[1] = data.len() as u8;
+        buffer[2..len].copy_from_slice(data);
+        if self.pec {
+            let crc = pec(0, &[self.address << 1]);
+            buffer[len] = pec(crc, &buffer[..len]);
+            len += 1;
+        }
+
+        &buffer[..len]
+    }
+
+    /// Perform an in-place diff between the shadow and desired registers, passing a block
+    /// write frame for each run of different registers to the transport, and copying each
+    /// run into the shadow once the transport returns successfully.
+    ///
+    /// # Arguments
+    /// * `shadow`      - The registers as last written.
+    /// * `desired`     - The desired registers.
+    /// * `transport`   - The function to call with the address and frame of each write.
+    pub fn try_sync_from<F, R, const N: usize>(
+        &self,
+        shadow: &mut [u8; N],
+        desired: &[u8; N],
+        transport: F,
+    ) -> Result<(), R>
+    where
+        F: FnMut(u8, &[u8]) -> Result<(), R>,
+    {
+        self.try_sync_from_with(shadow, desired, DiffOptions::new(), transport)
+    }
+
+    /// Perform an in-place diff between the shadow and desired registers, with runs shaped
+    /// by the given options, passing a block write frame for each run of different registers
+    /// to the transport, and copying each run into the shadow once the transport returns
+    /// successfully.
+    ///
+    /// Runs are always split to at most [`BLOCK_MAX`] bytes.
+    ///
+    /// # Arguments
+    /// * `shadow`      - The registers as last written.
+    /// * `desired`     - The desired registers.
+    /// * `options`     - The options controlling how runs are found.
+    /// * `transport`   - The function to call with the address and frame of each write.
+    ///
+    /// # Panics
+    /// Panics if there are more registers than a command can address, or if the options
+    /// align runs to more than [`BLOCK_MAX`] bytes.
+    pub fn try_sync_from_with<F, R, const N: usize>(
+        &self,
+        shadow: &mut [u8; N],
+        desired: &[u8; N],
+        mut options: DiffOptions,
+        mut transport: F,
+    ) -> Result<(), R>
+    where
+        F: FnMut(u8, &[u8]) -> Result<(), R>,
+    {
+        assert!(N <= 256, "more registers than a command can address");
+        assert!(
+            options.align <= BLOCK_MAX,
+            "aligned units larger than a block write"
+        );
+        options.max_run_len = options.max_run_len.min(BLOCK_MAX);
+
+        shadow.try_sync_from_with(desired, options, |idx, diff| {
+            let mut buffer = [0; FRAME_MAX];
+            transport(self.address, self.frame(idx as u8, diff, &mut buffer))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pec_vectors() {
+        // The check value of CRC-8/SMBUS
+        assert_eq!(pec(0, b"123456789"), 0xf4);
+        assert_eq!(pec(0, &[]), 0x00);
+        assert_eq!(pec(0, &[0x00]), 0x00);
+        assert_eq!(pec(0, &[0x01]), 0x07);
+        assert_eq!(pec(0, &[0xff]), 0xf3);
+
+        // A block write of 1, 2, 3 to command 0x20 of the device at 0x16
+        assert_eq!(pec(0, &[0x2c, 0x20, 0x03, 0x01, 0x02, 0x03]), 0xc6);
+    }
+
+    #[test]
+    fn test_smbus_frame() {
+        let mut buffer = [0; FRAME_MAX];
+        let writer = SmbusBlockWrite::new(0x16);
+        assert_eq!(
+            writer.frame(0x20, &[1, 2, 3], &mut buffer),
+            &[0x20, 3, 1, 2, 3]
+        );
+
+        let writer = writer.pec(true);
+        assert_eq!(
+            writer.frame(0x20, &[1, 2, 3], &mut buffer),
+            &[0x20, 3, 1, 2, 3, 0xc6]
+        );
+
+        let frame = writer.frame(0x00, &[0xaa; BLOCK_MAX], &mut buffer);
+        assert_eq!(frame.len(), FRAME_MAX);
+        assert_eq!(frame[1], 32);
+    }
+
+    #[test]
+    fn test_smbus_sync() {
+        let mut shadow = [0u8; 16];
+        let mut desired = [0u8; 16];
+
+        desired[2..4].copy_from_slice(&[1, 2]);
+        desired[10] = 3;
+
+        const EXPECTED_FRAMES: [&[u8]; 2] = [&[0x02, 2, 1, 2, 0x76], &[0x0a, 1, 3, 0xaa]];
+
+        let writer = SmbusBlockWrite::new(0x40).pec(true);
+        let mut call_idx = 0;
+        writer
+            .try_sync_from(&mut shadow, &desired, |address, frame| {
+                assert_eq!(address, 0x40);
+                assert_eq!(frame, EXPECTED_FRAMES[call_idx]);
+                call_idx += 1;
+                Ok::<_, ()>(())
+            })
+            .unwrap();
+        assert_eq!(call_idx, EXPECTED_FRAMES.len());
+        assert_eq!(shadow, desired);
+    }
+
+    #[test]
+    fn test_smbus_block_limit() {
+        let mut shadow = [0u8; 80];
+        let desired = [1u8; 80];
+
+        const EXPECTED_WRITES: [(u8, u8); 3] = [(0, 32), (32, 32), (64, 16)];
+
+        let writer = SmbusBlockWrite::new(0x40);
+        let mut call_idx = 0;
+        let result = writer.try_sync_from(&mut shadow, &desired, |_address, frame| {
+            assert_eq!((frame[0], frame[1]), EXPECTED_WRITES[call_idx]);
+            assert_eq!(frame.len(), usize::from(frame[1]) + 2);
+            call_idx += 1;
+
+            // The last write fails
+            if call_idx == 3 {
+                return Err(());
+            }
+            Ok(())
+        });
+        assert_eq!(result, Err(()));
+        assert_eq!(call_idx, EXPECTED_WRITES.len());
+        assert_eq!(&shadow[..64], &[1; 64]);
+        assert_eq!(&shadow[64..], &[0; 16]);
+
+        // A tighter limit from the options is kept
+        let options = DiffOptions::new().max_run_len(8);
+        let mut writes = 0;
+        writer
+            .try_sync_from_with(&mut shadow, &desired, options, |_address, frame| {
+                assert_eq!(frame[1], 8);
+                writes += 1;
+                Ok::<_, ()>(())
+            })
+            .unwrap();
+        assert_eq!(writes, 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "aligned units larger than a block write")]
+    fn test_smbus_align_too_large() {
+        let mut shadow = [0u8; 128];
+        let desired = [1u8; 128];
+
+        let options = DiffOptions::new().align(64);
+        let _ = SmbusBlockWrite::new(0x40).try_sync_from_with(
+            &mut shadow,
+            &desired,
+            options,
+            |_address, _frame| Ok::<_, ()>(()),
+        );
+    }
+}
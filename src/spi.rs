@@ -0,0 +1,401 @@
+use embedded_hal::spi::{Operation, SpiDevice};
+
+#[cfg(feature = "embedded-hal-async")]
+use embedded_hal_async::spi::SpiDevice as AsyncSpiDevice;
+
+use crate::{DiffInPlace, DiffOptions};
+
+/// How a write is framed, describing the header sent before the values of each run.
+///
+/// The header holds the address of the first register in its low bits, along with the bits
+/// marking it as a write and as a burst. It is one byte for addresses of up to 8 bits, and
+/// two big-endian bytes for wider addresses.
+///
+/// # Example
+/// ```
+///     use diff_in_place::Framing;
+///     // A 6-bit address, with the MSB set for writes and bit 6 set for bursts
+///     let framing = Framing::new(6).write_bits(0x80).burst_bits(0x40);
+///
+///     // A 15-bit address, with the MSB clear for writes
+///     let framing = Framing::new(15);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Framing {
+    address_bits: u32,
+    write_bits: u16,
+    burst_bits: u16,
+}
+
+impl Framing {
+    /// Create a framing with addresses of the given number of bits, and no write or burst
+    /// bits.
+    pub const fn new(address_bits: u32) -> Self {
+        assert!(
+            address_bits > 0 && address_bits <= 16,
+            "address_bits must be between 1 and 16"
+        );
+        Self {
+            address_bits,
+            write_bits: 0,
+            burst_bits: 0,
+        }
+    }
+
+    /// Set the bits of the header marking it as a write.
+    pub const fn write_bits(mut self, bits: u16) -> Self {
+        self.write_bits = bits;
+        self
+    }
+
+    /// Set the bits of the header marking it as a burst, set only when a run has more than
+    /// one register.
+    pub const fn burst_bits(mut self, bits: u16) -> Self {
+        self.burst_bits = bits;
+        self
+    }
+
+    /// Returns the highest register the address can hold.
+    pub const fn max_register(&self) -> usize {
+        // Shifted as a `u32`, since 16 address bits overflow a 16-bit `usize`
+        ((1u32 << self.address_bits) - 1) as usize
+    }
+
+    fn header_len(&self) -> usize {
+        if self.address_bits <= 8 && (self.write_bits | self.burst_bits) <= 0xff {
+            1
+        } else {
+            2
+        }
+    }
+
+    fn encode<'b>(&self, address: usize, len: usize, buffer: &'b mut [u8; 2]) -> &'b [u8] {
+        let mut header = address as u16 | self.write_bits;
+        if len > 1 {
+            header |= self.burst_bits;
+        }
+
+        *buffer = header.to_be_bytes();
+        &buffer[2 - self.header_len()..]
+    }
+}
+
+/// Keeps the registers of an SPI device in sync with a desired state, writing only the
+/// registers which differ from what was last written.
+///
+/// Each run of different registers is sent as a single burst transaction: the header of the
+/// first register as described by the [`Framing`], followed by the values of the run. The
+/// chip select is asserted for the whole transaction, and released between runs.
+///
+/// # Example
+/// ```
+///     # use embedded_hal::spi::{ErrorType, Operation, SpiDevice};
+///     # struct Device;
+///     # impl ErrorType for Device { type Error = core::convert::Infallible; }
+///     # impl SpiDevice for Device {
+///     #     fn transaction(&mut self, _: &mut [Operation<'_, u8>]) -> Result<(), Self::Error> {
+///     #         Ok(())
+///     #     }
+///     # }
+///     # let spi = Device;
+///     use diff_in_place::{Framing, SpiRegisterSync};
+///
+///     let framing = Framing::new(6).write_bits(0x80).burst_bits(0x40);
+///     let mut device = SpiRegisterSync::new(spi, framing, [0u8; 32]);
+///
+///     let mut config = [0u8; 32];
+///     config[0x10] = 0x0f;
+///     config[0x11] = 0x03;
+///
+///     // Sends 0xd0, 0x0f, 0x03
+///     device.sync(&config).unwrap();
+///     assert_eq!(device.shadow(), &config);
+/// ```
+#[derive(Debug)]
+pub struct SpiRegisterSync<SPI, const N: usize> {
+    spi: SPI,
+    framing: Framing,
+    shadow: [u8; N],
+}
+
+impl<SPI, const N: usize> SpiRegisterSync<SPI, N> {
+    /// Create a register sync for the given device.
+    ///
+    /// # Arguments
+    /// * `spi`     - The device, which handles its chip select.
+    /// * `framing` - How the device expects writes to be framed.
+    /// * `shadow`  - The current values of the registers of the device.
+    pub fn new(spi: SPI, framing: Framing, shadow: [u8; N]) -> Self {
+        assert!(
+            N.saturating_sub(1) <= framing.max_register(),
+            "more registers than the address can hold"
+        );
+        assert!(
+            (framing.write_bits | framing.burst_bits) as usize & framing.max_register() == 0,
+            "write and burst bits overlap the address"
+        );
+        Self {
+            spi,
+            framing,
+            shadow,
+        }
+    }
+
+    /// Returns the values of the registers as last written.
+    pub fn shadow(&self) -> &[u8; N] {
+        &self.shadow
+    }
+
+    /// Consumes the register sync, returning the device.
+    pub fn release(self) -> SPI {
+        self.spi
+    }
+}
+
+impl<SPI, const N: usize> SpiRegisterSync<SPI, N>
+where
+    SPI: SpiDevice,
+{
+    /// Write the registers which differ from the desired state, one transaction per run.
+    ///
+    /// If a transaction fails, the registers of the runs written before it are kept as
+    /// written, and the error of the device is returned.
+    ///
+    /// # Arguments
+    /// * `desired` - The desired values of the registers.
+    pub fn sync(&mut self, desired: &[u8; N]) -> Result<(), SPI::Error> {
+        self.sync_with(desired, DiffOptions::new())
+    }
+
+    /// Write the registers which differ from the desired state, with runs shaped by the given
+    /// options, one transaction per run.
+    ///
+    /// Devices limiting the length of a burst can be served by setting
+    /// [`DiffOptions::max_run_len`].
+    ///
+    /// # Arguments
+    /// * `desired` - The desired values of the registers.
+    /// * `options` - The options controlling how runs are found.
+    pub fn sync_with(&mut self, desired: &[u8; N], options: DiffOptions) -> Result<(), SPI::Error> {
+        let Self {
+            spi,
+            framing,
+            shadow,
+        } = self;
+
+        shadow.try_sync_from_with(desired, options, |idx, diff| {
+            let mut buffer = [0; 2];
+            let header = framing.encode(idx, diff.len(), &mut buffer);
+            spi.transaction(&mut [Operation::Write(header), Operation::Write(diff)])
+        })
+    }
+}
+
+#[cfg(feature = "embedded-hal-async")]
+impl<SPI, const N: usize> SpiRegisterSync<SPI, N>
+where
+    SPI: AsyncSpiDevice,
+{
+    /// Async version of `sync` for devices implementing the `embedded-hal-async` traits.
+    /// Write the registers which differ from the desired state, one transaction per run.
+    ///
+    /// # Arguments
+    /// * `desired` - The desired values of the registers.
+    pub async fn sync_async(&mut self, desired: &[u8; N]) -> Result<(), SPI::Error> {
+        self.sync_with_async(desired, DiffOptions::new()).await
+    }
+
+    /// Async version of `sync_with` for devices implementing the `embedded-hal-async` traits.
+    /// Write the registers which differ from the desired state, with runs shaped by the given
+    /// options, one transaction per run.
+    ///
+    /// # Arguments
+    /// * `desired` - The desired values of the registers.
+    /// * `options` - The options controlling how runs are found.
+    pub async fn sync_with_async(
+        &mut self,
+        desired: &[u8; N],
+        options: DiffOptions<'_>,
+    ) -> Result<(), SPI::Error> {
+        let Self {
+            spi,
+            framing,
+            shadow,
+        } = self;
+
+        shadow
+            .try_sync_from_with_async(desired, options, async |idx, diff| {
+                let mut buffer = [0; 2];
+                let header = framing.encode(idx, diff.len(), &mut buffer);
+                spi.transaction(&mut [Operation::Write(header), Operation::Write(diff)])
+                    .await
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal::spi::{ErrorKind, ErrorType};
+
+    /// A device recording the bytes written in each transaction, failing the transaction at
+    /// `fail_at`.
+    struct MockSpi {
+        transactions: [([u8; 8], usize); 4],
+        len: usize,
+        fail_at: Option<usize>,
+    }
+
+    impl MockSpi {
+        fn new() -> Self {
+            Self {
+                transactions: [([0; 8], 0); 4],
+                len: 0,
+                fail_at: None,
+            }
+        }
+
+        fn transactions(&self) -> impl Iterator<Item = &[u8]> {
+            self.transactions[..self.len]
+                .iter()
+                .map(|(data, len)| &data[..*len])
+        }
+    }
+
+    impl ErrorType for MockSpi {
+        type Error = ErrorKind;
+    }
+
+    impl SpiDevice for MockSpi {
+        fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Self::Error> {
+            if self.fail_at == Some(self.len) {
+                return Err(ErrorKind::ModeFault);
+            }
+
+            let (data, len) = &mut self.transactions[self.len];
+            for operation in operations {
+                match operation {
+                    Operation::Write(bytes) => {
+                        data[*len..*len + bytes.len()].copy_from_slice(bytes);
+                        *len += bytes.len();
+                    }
+                    _ => panic!("unexpected operation"),
+                }
+            }
+            self.len += 1;
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "embedded-hal-async")]
+    impl AsyncSpiDevice for MockSpi {
+        async fn transaction(
+            &mut self,
+            operations: &mut [Operation<'_, u8>],
+        ) -> Result<(), Self::Error> {
+            SpiDevice::transaction(self, operations)
+        }
+    }
+
+    fn assert_transactions(spi: &MockSpi, expected: &[&[u8]]) {
+        let mut transaction_idx = 0;
+        for data in spi.transactions() {
+            assert_eq!(data, expected[transaction_idx]);
+            transaction_idx += 1;
+        }
+        assert_eq!(transaction_idx, expected.len());
+    }
+
+    #[test]
+    fn test_spi_sync_write_and_burst_bits() {
+        let framing = Framing::new(6).write_bits(0x80).burst_bits(0x40);
+        let mut device = SpiRegisterSync::new(MockSpi::new(), framing, [0u8; 64]);
+
+        let mut desired = [0u8; 64];
+        desired[0x10..0x13].copy_from_slice(&[1, 2, 3]);
+        desired[0x3f] = 4;
+        device.sync(&desired).unwrap();
+        assert_eq!(device.shadow(), &desired);
+
+        // Nothing left to write
+        device.sync(&desired).unwrap();
+
+        assert_transactions(&device.release(), &[&[0xd0, 1, 2, 3], &[0xbf, 4]]);
+    }
+
+    #[test]
+    fn test_spi_sync_wide_address() {
+        // Writes have the MSB clear, so only the address is in the header
+        let mut device = SpiRegisterSync::new(MockSpi::new(), Framing::new(15), [0u8; 600]);
+
+        let mut desired = [0u8; 600];
+        desired[0x200..0x202].copy_from_slice(&[5, 6]);
+        device.sync(&desired).unwrap();
+
+        assert_transactions(&device.release(), &[&[0x02, 0x00, 5, 6]]);
+    }
+
+    #[test]
+    fn test_spi_sync_max_burst() {
+        let framing = Framing::new(7).burst_bits(0x80);
+        let mut device = SpiRegisterSync::new(MockSpi::new(), framing, [0u8; 16]);
+
+        let desired = [1u8, 2, 3, 4, 5, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let options = DiffOptions::new().max_run_len(2);
+        device.sync_with(&desired, options).unwrap();
+
+        assert_transactions(
+            &device.release(),
+            &[&[0x80, 1, 2], &[0x82, 3, 4], &[0x04, 5]],
+        );
+    }
+
+    #[test]
+    fn test_spi_sync_error() {
+        let mut spi = MockSpi::new();
+        spi.fail_at = Some(1);
+        let mut device = SpiRegisterSync::new(spi, Framing::new(8), [0u8; 8]);
+
+        let desired = [0u8, 1, 0, 0, 0, 2, 0, 0];
+        assert_eq!(device.sync(&desired), Err(ErrorKind::ModeFault));
+        assert_eq!(device.shadow(), &[0, 1, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_spi_address_limits() {
+        assert_eq!(Framing::new(7).max_register(), 0x7f);
+        assert_eq!(Framing::new(16).max_register(), 0xffff);
+
+        // Every register of a 7-bit address can be written
+        SpiRegisterSync::new(MockSpi::new(), Framing::new(7), [0u8; 128]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_spi_too_many_registers() {
+        SpiRegisterSync::new(MockSpi::new(), Framing::new(7), [0u8; 129]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_spi_overlapping_bits() {
+        let framing = Framing::new(7).write_bits(0x40);
+        SpiRegisterSync::new(MockSpi::new(), framing, [0u8; 128]);
+    }
+
+    #[test]
+    #[cfg(feature = "embedded-hal-async")]
+    fn test_spi_sync_async() {
+        let framing = Framing::new(6).write_bits(0x80).burst_bits(0x40);
+        let mut device = SpiRegisterSync::new(MockSpi::new(), framing, [0u8; 16]);
+
+        let mut desired = [0u8; 16];
+        desired[4..6].copy_from_slice(&[1, 2]);
+        desired[9] = 3;
+        crate::tests::block_on(device.sync_async(&desired)).unwrap();
+        assert_eq!(device.shadow(), &desired);
+
+        assert_transactions(&device.release(), &[&[0xc4, 1, 2], &[0x89, 3]]);
+    }
+}
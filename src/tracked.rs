@@ -0,0 +1,431 @@
+use core::convert::Infallible;
+use core::ops::{Deref, Index, IndexMut, Range};
+
+use crate::runs::Cursor;
+use crate::DiffOptions;
+
+/// An array which records which of its elements were written, so the runs to sync can be
+/// found without keeping a second copy of it.
+///
+/// Writes are recorded in a bitset of `WORDS` 32-bit words. If the array has more elements
+/// than the bitset has bits, each bit covers a block of consecutive elements, and a write to
+/// any of them makes the whole block dirty.
+///
+/// # Example
+/// ```
+///     use diff_in_place::Tracked;
+///     let mut state: Tracked<[u8; 10]> = Tracked::new([0; 10]);
+///
+///     state.set(2, 1);
+///     state.set(3, 2);
+///     state.set(5, 0);
+///     state[7] = 3;
+///
+///     state.flush(|idx, diff| {
+///         // println!("{}: {:?}", idx, diff);
+///         // Prints:
+///         // 2: [1, 2]
+///         // 7: [3]
+///     });
+///     assert!(!state.is_dirty());
+/// ```
+#[derive(Clone, Debug)]
+pub struct Tracked<A, const WORDS: usize = 4> {
+    inner: A,
+    dirty: [u32; WORDS],
+}
+
+impl<T, const N: usize, const WORDS: usize> Tracked<[T; N], WORDS> {
+    /// Create a tracked array with no dirty elements.
+    ///
+    /// # Arguments
+    /// * `inner`   - The array to track, which should match the current state.
+    pub const fn new(inner: [T; N]) -> Self {
+        assert!(WORDS > 0, "WORDS must be non-zero");
+        Self {
+            inner,
+            dirty: [0; WORDS],
+        }
+    }
+
+    /// Returns the number of elements covered by each bit of the bitset.
+    pub const fn block_len() -> usize {
+        let block_len = N.div_ceil(32 * WORDS);
+        if block_len == 0 {
+            1
+        } else {
+            block_len
+        }
+    }
+
+    /// Consumes the tracked array, returning the array.
+    pub fn into_inner(self) -> [T; N] {
+        self.inner
+    }
+
+    /// Set the element at the given index, marking it dirty only if its value changes.
+    ///
+    /// # Arguments
+    /// * `idx`     - The index of the element.
+    /// * `value`   - The new value of the element.
+    pub fn set(&mut self, idx: usize, value: T)
+    where
+        T: PartialEq,
+    {
+        if self.inner[idx] != value {
+            self.inner[idx] = value;
+            self.mark_dirty(idx..idx + 1);
+        }
+    }
+
+    /// Returns whether any element was written since the last flush.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.iter().any(|word| *word != 0)
+    }
+
+    /// Mark the given range of elements dirty, so they are emitted by the next flush.
+    ///
+    /// # Arguments
+    /// * `range`   - The range of elements to mark dirty.
+    pub fn mark_dirty(&mut self, range: Range<usize>) {
+        assert!(range.end <= N, "range out of bounds");
+        if range.is_empty() {
+            return;
+        }
+
+        let block_len = Self::block_len();
+        for block in range.start / block_len..range.end.div_ceil(block_len) {
+            self.dirty[block / 32] |= 1 << (block % 32);
+        }
+    }
+
+    /// Mark every element clean, as if it was just flushed.
+    pub fn mark_clean(&mut self) {
+        self.dirty = [0; WORDS];
+    }
+
+    /// Fallible version of `flush` for propagating errors.
+    /// Invoke the given function for each run of dirty elements, with the index into the
+    /// array and the slice of elements, marking the run clean once the function returns.
+    ///
+    /// If the function fails, the run it failed on and every run after it stay dirty.
+    ///
+    /// # Arguments
+    /// * `func`    - The function to call for each run of dirty elements.
+    pub fn try_flush<F, R>(&mut self, mut func: F) -> Result<(), R>
+    where
+        F: FnMut(usize, &[T]) -> Result<(), R>,
+    {
+        self.try_for_each_dirty(|inner, span| func(span.start, &inner[span]))
+    }
+
+    /// Invoke the given function for each run of dirty elements, with the index into the
+    /// array and the slice of elements, marking every element clean.
+    ///
+    /// The runs cover every element changed since the last flush, but also elements written
+    /// back to their value at the last flush. Use [`flush_against`](Self::flush_against) to
+    /// get only the elements which really changed.
+    ///
+    /// # Arguments
+    /// * `func`    - The function to call for each run of dirty elements.
+    pub fn flush<F>(&mut self, mut func: F)
+    where
+        F: FnMut(usize, &[T]),
+    {
+        self.try_flush(|idx, diff| -> Result<(), Infallible> {
+            func(idx, diff);
+            Ok(())
+        })
+        .unwrap_or_else(|never| match never {})
+    }
+
+    /// Fallible version of `flush_against` for propagating errors.
+    /// Compare only the dirty elements against a shadow copy, invoking the given function
+    /// for each run of elements which really changed, with the index into the array and
+    /// the slice of elements, and updating the shadow copy with it once the function returns.
+    ///
+    /// If the function fails, the run it failed on and every run after it stay dirty.
+    ///
+    /// # Arguments
+    /// * `shadow`  - The copy of the array as it was at the last flush.
+    /// * `func`    - The function to call for each run of changed elements.
+    pub fn try_flush_against<F, R>(&mut self, shadow: &mut [T; N], mut func: F) -> Result<(), R>
+    where
+        T: PartialEq + Clone,
+        F: FnMut(usize, &[T]) -> Result<(), R>,
+    {
+        self.try_for_each_dirty(|inner, span| {
+            let left = &mut shadow[span.clone()];
+            let right = &inner[span.clone()];
+
+            let mut cursor = Cursor::new(DiffOptions::new());
+            while let Some(run) = cursor.next_run(left, right) {
+                func(span.start + run.start, &right[run.clone()])?;
+                left[run.clone()].clone_from_slice(&right[run]);
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Compare only the dirty elements against a shadow copy, invoking the given function
+    /// for each run of elements which really changed, with the index into the array and
+    /// the slice of elements, and updating the shadow copy.
+    ///
+    /// Unlike [`flush`](Self::flush), elements written with their current value, or sharing
+    /// a block with a written element, are not emitted. As long as the shadow copy
+    /// only changes through this function, the runs are the same as those of
+    /// [`DiffInPlace::diff_in_place`](crate::DiffInPlace::diff_in_place) against it, but only
+    /// the dirty blocks are compared.
+    ///
+    /// # Arguments
+    /// * `shadow`  - The copy of the array as it was at the last flush.
+    /// * `func`    - The function to call for each run of changed elements.
+    ///
+    /// # Example
+    /// ```
+    ///     use diff_in_place::Tracked;
+    ///     let mut shadow = [0u8; 256];
+    ///     let mut state: Tracked<_, 1> = Tracked::new(shadow);
+    ///
+    ///     state[10] = 0;
+    ///     state[100] = 1;
+    ///     state[101] = 2;
+    ///
+    ///     state.flush_against(&mut shadow, |idx, diff| {
+    ///         // println!("{}: {:?}", idx, diff);
+    ///         // Prints:
+    ///         // 100: [1, 2]
+    ///     });
+    ///     assert_eq!(shadow, *state);
+    /// ```
+    pub fn flush_against<F>(&mut self, shadow: &mut [T; N], mut func: F)
+    where
+        T: PartialEq + Clone,
+        F: FnMut(usize, &[T]),
+    {
+        self.try_flush_against(shadow, |idx, diff| -> Result<(), Infallible> {
+            func(idx, diff);
+            Ok(())
+        })
+        .unwrap_or_else(|never| match never {})
+    }
+
+    /// Invoke the given function for each span of consecutive dirty blocks, marking the
+    /// span clean once the function returns.
+    fn try_for_each_dirty<F, R>(&mut self, mut func: F) -> Result<(), R>
+    where
+        F: FnMut(&[T; N], Range<usize>) -> Result<(), R>,
+    {
+        let block_len = Self::block_len();
+        let blocks = N.div_ceil(block_len);
+        let mut block = 0;
+        while block < blocks {
+            if !self.is_block_dirty(block) {
+                block += 1;
+                continue;
+            }
+
+            let start = block;
+            while block < blocks && self.is_block_dirty(block) {
+                block += 1;
+            }
+
+            func(&self.inner, start * block_len..(block * block_len).min(N))?;
+            for block in start..block {
+                self.dirty[block / 32] &= !(1 << (block % 32));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_block_dirty(&self, block: usize) -> bool {
+        self.dirty[block / 32] & (1 << (block % 32)) != 0
+    }
+}
+
+impl<T, const N: usize, const WORDS: usize> Deref for Tracked<[T; N], WORDS> {
+    type Target = [T; N];
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<T, const N: usize, const WORDS: usize> Index<usize> for Tracked<[T; N], WORDS> {
+    type Output = T;
+
+    fn index(&self, idx: usize) -> &Self::Output {
+        &self.inner[idx]
+    }
+}
+
+/// Mutable indexing marks the element dirty, whether or not its value is changed.
+impl<T, const N: usize, const WORDS: usize> IndexMut<usize> for Tracked<[T; N], WORDS> {
+    fn index_mut(&mut self, idx: usize) -> &mut Self::Output {
+        self.mark_dirty(idx..idx + 1);
+        &mut self.inner[idx]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DiffInPlace;
+
+    #[test]
+    fn test_tracked_matches_diff() {
+        let a = [0u8; 40];
+        let mut b = [0u8; 40];
+
+        b[..10].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+        b[20..25].copy_from_slice(&[11, 12, 13, 14, 15]);
+        b[39] = 20;
+
+        let mut tracked: Tracked<_, 2> = Tracked::new(a);
+        for (idx, value) in b.iter().enumerate() {
+            tracked.set(idx, *value);
+        }
+
+        let mut runs = a.diff_runs(&b);
+        tracked.flush(|idx, diff| {
+            assert_eq!(runs.next(), Some((idx, diff)));
+        });
+        assert_eq!(runs.next(), None);
+        assert!(!tracked.is_dirty());
+        assert_eq!(tracked.into_inner(), b);
+    }
+
+    #[test]
+    fn test_tracked_index_mut() {
+        let mut tracked: Tracked<_> = Tracked::new([0u8; 8]);
+        assert_eq!(Tracked::<[u8; 8]>::block_len(), 1);
+
+        tracked[1] = 1;
+        tracked[2] = 0;
+        tracked[6] += 2;
+        assert_eq!(tracked[6], 2);
+
+        const EXPECTED_CALLS: [(usize, &[u8]); 2] = [(1usize, &[1, 0]), (6usize, &[2])];
+
+        let mut call_idx = 0;
+        tracked.flush(|idx, diff| {
+            let (expected_idx, expected_diff) = EXPECTED_CALLS[call_idx];
+            assert_eq!(idx, expected_idx);
+            assert_eq!(diff, expected_diff);
+            call_idx += 1;
+        });
+        assert_eq!(call_idx, EXPECTED_CALLS.len());
+    }
+
+    #[test]
+    fn test_tracked_blocks() {
+        // 100 elements in 32 bits, so each bit covers 4 elements
+        let mut tracked: Tracked<_, 1> = Tracked::new([0u8; 100]);
+        assert_eq!(Tracked::<[u8; 100], 1>::block_len(), 4);
+
+        tracked.set(5, 1);
+        tracked.set(8, 2);
+        tracked.set(98, 3);
+
+        const EXPECTED_CALLS: [(usize, &[u8]); 2] = [
+            (4usize, &[0, 1, 0, 0, 2, 0, 0, 0]),
+            (96usize, &[0, 0, 3, 0]),
+        ];
+
+        let mut call_idx = 0;
+        tracked.flush(|idx, diff| {
+            let (expected_idx, expected_diff) = EXPECTED_CALLS[call_idx];
+            assert_eq!(idx, expected_idx);
+            assert_eq!(diff, expected_diff);
+            call_idx += 1;
+        });
+        assert_eq!(call_idx, EXPECTED_CALLS.len());
+    }
+
+    #[test]
+    fn test_tracked_failed_flush_stays_dirty() {
+        let mut tracked: Tracked<_> = Tracked::new([0u8; 10]);
+        tracked.set(1, 1);
+        tracked.set(5, 2);
+
+        let result = tracked.try_flush(|idx, _diff| if idx == 5 { Err(()) } else { Ok(()) });
+        assert_eq!(result, Err(()));
+
+        let mut call_idx = 0;
+        tracked.flush(|idx, diff| {
+            assert_eq!((idx, diff), (5, &[2][..]));
+            call_idx += 1;
+        });
+        assert_eq!(call_idx, 1);
+    }
+
+    #[test]
+    fn test_tracked_flush_against() {
+        let mut shadow = [0u8; 200];
+        let mut b = shadow;
+
+        b[3..9].copy_from_slice(&[1, 2, 3, 4, 5, 6]);
+        b[13] = 7;
+        b[150] = 8;
+
+        // 200 elements in 32 bits, so each bit covers 7 elements
+        let mut tracked: Tracked<_, 1> = Tracked::new(shadow);
+        for (idx, value) in b.iter().enumerate() {
+            tracked[idx] = *value;
+        }
+
+        let a = shadow;
+        let mut runs = a.diff_runs(&b);
+        let mut expected = [(0, &[][..]); 3];
+        for run in expected.iter_mut() {
+            *run = runs.next().unwrap();
+        }
+        assert_eq!(runs.next(), None);
+
+        let mut call_idx = 0;
+        tracked.flush_against(&mut shadow, |idx, diff| {
+            assert_eq!((idx, diff), expected[call_idx]);
+            call_idx += 1;
+        });
+        assert_eq!(call_idx, expected.len());
+        assert_eq!(shadow, b);
+        assert!(!tracked.is_dirty());
+
+        // Writing the same values is dirty, but does not change anything
+        tracked[150] = 8;
+        tracked.mark_dirty(0..200);
+        tracked.flush_against(&mut shadow, |_idx, _diff| unreachable!());
+    }
+
+    #[test]
+    fn test_tracked_failed_flush_against() {
+        let mut shadow = [0u8; 10];
+        let mut tracked: Tracked<_> = Tracked::new(shadow);
+        tracked.set(1, 1);
+        tracked.set(5, 2);
+
+        let result =
+            tracked.try_flush_against(
+                &mut shadow,
+                |idx, _diff| {
+                    if idx == 5 {
+                        Err(())
+                    } else {
+                        Ok(())
+                    }
+                },
+            );
+        assert_eq!(result, Err(()));
+        assert_eq!(shadow, [0, 1, 0, 0, 0, 0, 0, 0, 0, 0]);
+
+        let mut call_idx = 0;
+        tracked.flush_against(&mut shadow, |idx, diff| {
+            assert_eq!((idx, diff), (5, &[2][..]));
+            call_idx += 1;
+        });
+        assert_eq!(call_idx, 1);
+        assert_eq!(shadow, *tracked);
+    }
+}
@@ -0,0 +1,474 @@
+//! A compact, versioned binary encoding of the runs of a diff between byte arrays.
+//!
+//! This is meant for sending deltas over slow or lossy links, such as LoRa or BLE,
+//! and for logging them to flash.
+//!
+//! # Format
+//! All integers are unsigned LEB128 varints, unless noted otherwise:
+//! ```text
+//! patch   = version len count run*
+//! version = 0x01, a single byte
+//! len     = the length of the array the patch applies to
+//! count   = the number of runs which follow
+//! run     = skip length payload
+//! skip    = the offset of the run from the end of the previous run,
+//!           or from the start of the array for the first run
+//! length  = the number of bytes in the run, never zero
+//! payload = the bytes of the run
+//! ```
+//! Since runs are placed relative to the end of the previous run, they are always in order
+//! and can never overlap.
+//!
+//! # Example
+//! ```
+//!     use diff_in_place::wire;
+//!     let base = [0u8; 10];
+//!     let target = [0, 0, 1, 2, 0, 0, 0, 3, 4, 5];
+//!
+//!     let mut buffer = [0u8; 16];
+//!     let len = wire::encode_into(&base, &target, &mut buffer).unwrap();
+//!     assert_eq!(&buffer[..len], &[0x01, 10, 2, 2, 2, 1, 2, 3, 3, 3, 4, 5]);
+//!
+//!     let mut state = base;
+//!     for (idx, diff) in wire::decode::<10>(&buffer[..len]).unwrap() {
+//!         state[idx..idx + diff.len()].copy_from_slice(diff);
+//!     }
+//!     assert_eq!(state, target);
+//! ```
+
+use core::fmt;
+use core::iter::FusedIterator;
+
+use crate::DiffInPlace;
+
+/// The version of the format written by this module.
+pub const VERSION: u8 = 0x01;
+
+/// The error returned when encoding runs fails.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EncodeError {
+    /// The output buffer is too small for the encoded runs.
+    BufferTooSmall,
+    /// A run is empty, out of order, overlaps the previous run or ends past the end of the
+    /// array.
+    InvalidRuns,
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodeError::BufferTooSmall => f.write_str("buffer too small for encoded runs"),
+            EncodeError::InvalidRuns => f.write_str("runs are not valid for the array"),
+        }
+    }
+}
+
+/// The error returned when decoding runs fails.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The encoding is of an unsupported version.
+    UnsupportedVersion(u8),
+    /// The input ends before the encoded runs do.
+    Truncated,
+    /// A varint does not fit in a `usize`.
+    Overflow,
+    /// The encoded runs are for an array of a different length.
+    LengthMismatch,
+    /// A run ends past the end of the array.
+    OutOfRange,
+    /// A run has no bytes.
+    EmptyRun,
+    /// The input continues after the encoded runs.
+    TrailingBytes,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnsupportedVersion(version) => {
+                write!(f, "unsupported encoding version {}", version)
+            }
+            DecodeError::Truncated => f.write_str("encoded runs are truncated"),
+            DecodeError::Overflow => f.write_str("varint overflows usize"),
+            DecodeError::LengthMismatch => f.write_str("encoded runs are for a different length"),
+            DecodeError::OutOfRange => f.write_str("run ends past the end of the array"),
+            DecodeError::EmptyRun => f.write_str("run has no bytes"),
+            DecodeError::TrailingBytes => f.write_str("trailing bytes after encoded runs"),
+        }
+    }
+}
+
+/// Encode the runs of the diff between `base` and `target` into `out`, returning the number
+/// of bytes written.
+///
+/// # Arguments
+/// * `base`    - The array the runs apply to.
+/// * `target`  - The array applying the runs results in.
+/// * `out`     - The buffer to encode into.
+pub fn encode_into<const N: usize>(
+    base: &[u8; N],
+    target: &[u8; N],
+    out: &mut [u8],
+) -> Result<usize, EncodeError> {
+    encode_runs_into(N, base.diff_runs(target), out)
+}
+
+/// Encode the given runs into `out`, returning the number of bytes written.
+///
+/// The runs must be non-empty, in order, must not overlap and must end within the array,
+/// as is the case for the runs of [`DiffInPlace::diff_runs`],
+/// [`DiffInPlace::diff_runs_with`] and [`Patch::runs`](crate::Patch::runs). Otherwise
+/// [`EncodeError::InvalidRuns`] is returned.
+///
+/// # Arguments
+/// * `len`     - The length of the array the runs apply to.
+/// * `runs`    - The runs to encode.
+/// * `out`     - The buffer to encode into.
+pub fn encode_runs_into<'r, I>(len: usize, runs: I, out: &mut [u8]) -> Result<usize, EncodeError>
+where
+    I: IntoIterator<Item = (usize, &'r [u8])>,
+    I::IntoIter: Clone,
+{
+    let runs = runs.into_iter();
+    let mut writer = Writer { out, written: 0 };
+    writer.bytes(&[VERSION])?;
+    writer.varint(len)?;
+    writer.varint(runs.clone().count())?;
+
+    let mut end = 0;
+    for (idx, diff) in runs {
+        let run_end = run_end(end, len, idx, diff)?;
+        writer.varint(idx - end)?;
+        writer.varint(diff.len())?;
+        writer.bytes(diff)?;
+        end = run_end;
+    }
+
+    Ok(writer.written)
+}
+
+/// Returns the number of bytes [`encode_runs_into`] writes for the given runs.
+///
+/// # Arguments
+/// * `len`     - The length of the array the runs apply to.
+/// * `runs`    - The runs to encode.
+pub fn encoded_len<'r, I>(len: usize, runs: I) -> Result<usize, EncodeError>
+where
+    I: IntoIterator<Item = (usize, &'r [u8])>,
+{
+    let mut count = 0;
+    let mut encoded = 1 + varint_len(len);
+    let mut end = 0;
+    for (idx, diff) in runs {
+        let run_end = run_end(end, len, idx, diff)?;
+        encoded += varint_len(idx - end) + varint_len(diff.len()) + diff.len();
+        end = run_end;
+        count += 1;
+    }
+
+    Ok(encoded + varint_len(count))
+}
+
+/// Returns the end of the given run, checking it can be encoded after a run ending at `end`
+/// in an array of `len` bytes.
+fn run_end(end: usize, len: usize, idx: usize, diff: &[u8]) -> Result<usize, EncodeError> {
+    match idx.checked_add(diff.len()) {
+        Some(run_end) if idx >= end && !diff.is_empty() && run_end <= len => Ok(run_end),
+        _ => Err(EncodeError::InvalidRuns),
+    }
+}
+
+/// Decode and validate runs for an array of `N` bytes.
+///
+/// The whole input is validated before any run is returned, so a corrupt input is never
+/// partially applied.
+///
+/// # Arguments
+/// * `bytes`   - The encoded runs.
+pub fn decode<const N: usize>(bytes: &[u8]) -> Result<WireRuns<'_>, DecodeError> {
+    let mut reader = Reader { bytes };
+    let version = reader.take(1)?[0];
+    if version != VERSION {
+        return Err(DecodeError::UnsupportedVersion(version));
+    }
+    if reader.varint()? != N {
+        return Err(DecodeError::LengthMismatch);
+    }
+    let count = reader.varint()?;
+
+    let runs = WireRuns {
+        reader: reader.clone(),
+        len: N,
+        end: 0,
+        remaining: count,
+    };
+
+    // Walk over all of the runs once to validate them
+    let mut end = 0;
+    for _ in 0..count {
+        let (idx, diff) = reader.run(end, N)?;
+        end = idx + diff.len();
+    }
+    if !reader.bytes.is_empty() {
+        return Err(DecodeError::TrailingBytes);
+    }
+
+    Ok(runs)
+}
+
+/// An iterator over validated encoded runs, with the index of each run and its bytes.
+///
+/// Created by [`decode`].
+#[derive(Clone, Debug)]
+pub struct WireRuns<'b> {
+    reader: Reader<'b>,
+    len: usize,
+    end: usize,
+    remaining: usize,
+}
+
+impl<'b> Iterator for WireRuns<'b> {
+    type Item = (usize, &'b [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        // The runs were already validated, so this cannot fail
+        let (idx, diff) = self.reader.run(self.end, self.len).ok()?;
+        self.end = idx + diff.len();
+        self.remaining -= 1;
+        Some((idx, diff))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for WireRuns<'_> {}
+
+impl FusedIterator for WireRuns<'_> {}
+
+fn varint_len(mut value: usize) -> usize {
+    let mut len = 1;
+    while value >= 0x80 {
+        value >>= 7;
+        len += 1;
+    }
+    len
+}
+
+struct Writer<'o> {
+    out: &'o mut [u8],
+    written: usize,
+}
+
+impl Writer<'_> {
+    fn bytes(&mut self, bytes: &[u8]) -> Result<(), EncodeError> {
+        let end = self.written + bytes.len();
+        self.out
+            .get_mut(self.written..end)
+            .ok_or(EncodeError::BufferTooSmall)?
+            .copy_from_slice(bytes);
+        self.written = end;
+        Ok(())
+    }
+
+    fn varint(&mut self, mut value: usize) -> Result<(), EncodeError> {
+        while value >= 0x80 {
+            self.bytes(&[(value as u8) | 0x80])?;
+            value >>= 7;
+        }
+        self.bytes(&[value as u8])
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Reader<'b> {
+    bytes: &'b [u8],
+}
+
+impl<'b> Reader<'b> {
+    fn take(&mut self, len: usize) -> Result<&'b [u8], DecodeError> {
+        if self.bytes.len() < len {
+            return Err(DecodeError::Truncated);
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn varint(&mut self) -> Result<usize, DecodeError> {
+        let mut value: usize = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.take(1)?[0];
+            let bits = usize::from(byte & 0x7f);
+            if shift >= usize::BITS || (bits << shift) >> shift != bits {
+                return Err(DecodeError::Overflow);
+            }
+            value |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+            shift += 7;
+        }
+    }
+
+    fn run(&mut self, end: usize, len: usize) -> Result<(usize, &'b [u8]), DecodeError> {
+        let idx = end
+            .checked_add(self.varint()?)
+            .ok_or(DecodeError::OutOfRange)?;
+        let run_len = self.varint()?;
+        if run_len == 0 {
+            return Err(DecodeError::EmptyRun);
+        }
+        match idx.checked_add(run_len) {
+            Some(run_end) if run_end <= len => Ok((idx, self.take(run_len)?)),
+            _ => Err(DecodeError::OutOfRange),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DiffOptions;
+
+    #[test]
+    fn test_wire_round_trip() {
+        let a = [0u8; 300];
+        let mut b = [0u8; 300];
+
+        b[..10].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+        b[200..205].copy_from_slice(&[11, 12, 13, 14, 15]);
+        b[299] = 20;
+
+        let mut buffer = [0u8; 64];
+        let len = encode_into(&a, &b, &mut buffer).unwrap();
+        assert_eq!(Ok(len), encoded_len(300, a.diff_runs(&b)));
+
+        let mut runs = a.diff_runs(&b);
+        for run in decode::<300>(&buffer[..len]).unwrap() {
+            assert_eq!(runs.next(), Some(run));
+        }
+        assert_eq!(runs.next(), None);
+    }
+
+    #[test]
+    fn test_wire_adjacent_runs() {
+        let a = [0u8; 16];
+        let b = [1u8; 16];
+
+        let options = DiffOptions::new().max_run_len(4);
+        let mut buffer = [0u8; 32];
+        let len = encode_runs_into(16, a.diff_runs_with(&b, options), &mut buffer).unwrap();
+
+        let mut runs = a.diff_runs_with(&b, options);
+        for run in decode::<16>(&buffer[..len]).unwrap() {
+            assert_eq!(runs.next(), Some(run));
+        }
+        assert_eq!(runs.next(), None);
+    }
+
+    #[test]
+    fn test_wire_empty() {
+        let a = [0u8; 16];
+
+        let mut buffer = [0u8; 4];
+        let len = encode_into(&a, &a, &mut buffer).unwrap();
+        assert_eq!(&buffer[..len], &[VERSION, 16, 0]);
+        assert_eq!(decode::<16>(&buffer[..len]).unwrap().next(), None);
+    }
+
+    #[test]
+    fn test_wire_buffer_too_small() {
+        let a = [0u8; 16];
+        let b = [1u8; 16];
+
+        let mut buffer = [0u8; 16];
+        assert_eq!(
+            encode_into(&a, &b, &mut buffer),
+            Err(EncodeError::BufferTooSmall)
+        );
+    }
+
+    #[test]
+    fn test_wire_encode_rejects_invalid() {
+        let mut buffer = [0u8; 32];
+        let invalid: [&[(usize, &[u8])]; 5] = [
+            // Overlapping
+            &[(2, &[1, 2]), (3, &[3])],
+            // Out of order
+            &[(8, &[1]), (2, &[2])],
+            // Past the end of the array
+            &[(20, &[1])],
+            &[(usize::MAX, &[1])],
+            // Empty
+            &[(2, &[])],
+        ];
+        for runs in invalid {
+            let runs = runs.iter().copied();
+            assert_eq!(
+                encode_runs_into(16, runs.clone(), &mut buffer),
+                Err(EncodeError::InvalidRuns)
+            );
+            assert_eq!(encoded_len(16, runs), Err(EncodeError::InvalidRuns));
+        }
+    }
+
+    #[test]
+    fn test_wire_rejects_truncated() {
+        let a = [0u8; 16];
+        let mut b = [0u8; 16];
+
+        b[3..6].copy_from_slice(&[1, 2, 3]);
+        b[10] = 4;
+
+        let mut buffer = [0u8; 32];
+        let len = encode_into(&a, &b, &mut buffer).unwrap();
+        for truncated in 0..len {
+            assert_eq!(
+                decode::<16>(&buffer[..truncated]).err(),
+                Some(DecodeError::Truncated)
+            );
+        }
+        assert_eq!(
+            decode::<16>(&buffer[..len + 1]).err(),
+            Some(DecodeError::TrailingBytes)
+        );
+    }
+
+    #[test]
+    fn test_wire_rejects_invalid() {
+        assert_eq!(
+            decode::<4>(&[0x02, 4, 0]).err(),
+            Some(DecodeError::UnsupportedVersion(0x02))
+        );
+        assert_eq!(
+            decode::<4>(&[VERSION, 5, 0]).err(),
+            Some(DecodeError::LengthMismatch)
+        );
+        assert_eq!(
+            decode::<4>(&[VERSION, 4, 1, 2, 3, 1, 2, 3]).err(),
+            Some(DecodeError::OutOfRange)
+        );
+        assert_eq!(
+            decode::<4>(&[VERSION, 4, 2, 0, 2, 1, 2, 0, 0]).err(),
+            Some(DecodeError::EmptyRun)
+        );
+        assert_eq!(
+            decode::<4>(&[VERSION, 4, 2, 0, 2, 1, 2, 1, 2, 3, 4]).err(),
+            Some(DecodeError::OutOfRange)
+        );
+        assert_eq!(
+            decode::<4>(&[
+                VERSION, 4, 1, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f
+            ])
+            .err(),
+            Some(DecodeError::Overflow)
+        );
+    }
+}